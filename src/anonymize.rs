@@ -0,0 +1,67 @@
+use crate::generate::{self, Random};
+use openssl::sha::sha256;
+
+/// How a column value is rewritten.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Function {
+    /// Hex SHA-256 of the value. Stable, so joins on the column keep working.
+    Hash,
+    /// Keeps the first and last character, e.g. `j******e`. E-mails keep their domain.
+    Mask,
+    /// A plausible fake value picked from the column name, e.g. a name or an e-mail. Stable per value.
+    Fake,
+}
+
+impl Function {
+    pub fn parse(value: &str) -> anyhow::Result<Function> {
+        match value {
+            "hash" => Ok(Function::Hash),
+            "mask" => Ok(Function::Mask),
+            "fake" => Ok(Function::Fake),
+            _ => Err(anyhow::anyhow!("Unknown anonymization function: [{value}]. Use hash, mask or fake")),
+        }
+    }
+
+    pub(crate) fn apply(self, column: &str, value: &str) -> String {
+        match self {
+            Function::Hash => hex(&sha256(value.as_bytes())),
+            Function::Mask => mask(value),
+            Function::Fake => {
+                let digest = sha256(value.as_bytes());
+                let seed = u64::from_le_bytes(digest[..8].try_into().unwrap_or_default());
+                generate::text(column, (seed % 100_000) as usize, &mut Random::new(seed))
+            }
+        }
+    }
+}
+
+/// Columns to rewrite and how, e.g. `email=hash,name=fake`.
+pub fn rules(value: &str) -> anyhow::Result<Vec<(String, Function)>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|rule| !rule.is_empty())
+        .map(|rule| {
+            let (column, function) = rule
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("Invalid anonymization rule: [{rule}]. Use column=hash|mask|fake"))?;
+            Ok((column.trim().to_lowercase(), Function::parse(function.trim())?))
+        })
+        .collect()
+}
+
+fn mask(value: &str) -> String {
+    if let Some((local, domain)) = value.split_once('@') {
+        return format!("{}@{domain}", mask(local));
+    }
+
+    let chars: Vec<char> = value.chars().collect();
+    match chars.len() {
+        0..=2 => "*".repeat(chars.len()),
+        n => format!("{}{}{}", chars[0], "*".repeat(n - 2), chars[n - 1]),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
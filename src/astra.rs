@@ -0,0 +1,212 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use openssl::pkey::PKey;
+use openssl::ssl::{SslConnector, SslMethod};
+use openssl::x509::X509;
+use scylla::transport::session::{AddressTranslator, TranslationError};
+use scylla::transport::topology::UntranslatedPeer;
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::pin::Pin;
+use std::process::Command;
+use tokio::io::{copy_bidirectional, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+use tokio_openssl::SslStream;
+
+/// A DataStax Astra secure connect bundle: the zip downloaded from the Astra console.
+#[derive(Clone)]
+pub struct Bundle {
+    pub path: String,
+    /// `host:port` of the metadata service listing the SNI proxy and the nodes.
+    metadata: String,
+    /// Default keyspace of the database, if the bundle names one.
+    pub keyspace: Option<String>,
+    connector: SslConnector,
+}
+
+impl std::fmt::Debug for Bundle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Bundle").field("path", &self.path).field("metadata", &self.metadata).finish()
+    }
+}
+
+/// Reads the secure connect bundle at `path`. The zip is read with `unzip`, which must be on the PATH.
+pub fn bundle(path: &str) -> Result<Bundle> {
+    let config = String::from_utf8(entry(path, "config.json")?)
+        .map_err(|_| anyhow::anyhow!("Invalid config.json in Astra bundle [{path}]"))?;
+    let host = json_string(&config, "host").ok_or_else(|| anyhow::anyhow!("No host in Astra bundle [{path}]"))?;
+    let port = json_number(&config, "port").ok_or_else(|| anyhow::anyhow!("No port in Astra bundle [{path}]"))?;
+
+    let mut connector = SslConnector::builder(SslMethod::tls())?;
+    for ca in X509::stack_from_pem(&entry(path, "ca.crt")?)? {
+        connector.cert_store_mut().add_cert(ca)?;
+    }
+    let cert = X509::from_pem(&entry(path, "cert")?)?;
+    connector.set_certificate(&cert)?;
+    let key = PKey::private_key_from_pem(&entry(path, "key")?)?;
+    connector.set_private_key(&key)?;
+    connector.check_private_key()?;
+
+    Ok(Bundle {
+        path: path.to_string(),
+        metadata: format!("{host}:{port}"),
+        keyspace: json_string(&config, "keyspace"),
+        connector: connector.build(),
+    })
+}
+
+/// Contents of the `name` file of the zip at `path`.
+fn entry(path: &str, name: &str) -> Result<Vec<u8>> {
+    let output = Command::new("unzip")
+        .args(["-p", path, name])
+        .output()
+        .map_err(|e| anyhow::anyhow!("Unable to run unzip on Astra bundle [{path}]: {e}"))?;
+    if !output.status.success() || output.stdout.is_empty() {
+        return Err(anyhow::anyhow!("No {name} in Astra bundle [{path}]"));
+    }
+
+    Ok(output.stdout)
+}
+
+/// Routes every cluster connection through the SNI proxy of an Astra database.
+///
+/// Astra exposes all its nodes behind a single TLS endpoint, picking the node from the server name of
+/// the handshake. Like [`crate::proxy::Proxy`], each node gets a local listener, which opens the TLS
+/// connection naming the node, and peers discovered from the cluster are translated to their listener.
+pub(crate) struct Astra {
+    sni_proxy: String,
+    connector: SslConnector,
+    /// The nodes to start from, by host id.
+    pub(crate) contact_points: Vec<String>,
+    pub(crate) local_dc: Option<String>,
+    forwards: Mutex<HashMap<String, SocketAddr>>,
+}
+
+impl Astra {
+    /// Asks the metadata service of the bundle for the SNI proxy and the nodes.
+    pub(crate) async fn start(bundle: &Bundle) -> Result<Astra> {
+        let (host, _) = bundle.metadata.rsplit_once(':').unwrap_or((&bundle.metadata, ""));
+        let tcp = TcpStream::connect(&bundle.metadata).await?;
+        let mut stream = SslStream::new(bundle.connector.configure()?.into_ssl(host)?, tcp)?;
+        Pin::new(&mut stream)
+            .connect()
+            .await
+            .map_err(|e| anyhow::anyhow!("Unable to reach the Astra metadata service [{}]: {e}", bundle.metadata))?;
+
+        let request = format!("GET /metadata HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", bundle.metadata);
+        stream.write_all(request.as_bytes()).await?;
+        let mut response = vec![];
+        // the service closes the connection without a TLS close_notify
+        _ = stream.read_to_end(&mut response).await;
+        let response = String::from_utf8_lossy(&response);
+        let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+        if !head.starts_with("HTTP/1.1 200") {
+            let status = head.lines().next().unwrap_or_default();
+            return Err(anyhow::anyhow!("Astra metadata service [{}] replied [{status}]", bundle.metadata));
+        }
+
+        let sni_proxy = json_string(body, "sni_proxy_address")
+            .ok_or_else(|| anyhow::anyhow!("No SNI proxy in the Astra metadata: [{body}]"))?;
+        let contact_points = json_strings(body, "contact_points");
+        if contact_points.is_empty() {
+            return Err(anyhow::anyhow!("No contact points in the Astra metadata: [{body}]"));
+        }
+
+        Ok(Astra {
+            sni_proxy,
+            connector: bundle.connector.clone(),
+            contact_points,
+            local_dc: json_string(body, "local_dc"),
+            forwards: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Returns the local address forwarding to the node `host_id`, starting a listener if needed.
+    pub(crate) async fn local_address(&self, host_id: &str) -> Result<SocketAddr> {
+        let mut forwards = self.forwards.lock().await;
+        if let Some(address) = forwards.get(host_id) {
+            return Ok(*address);
+        }
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let address = listener.local_addr()?;
+        let sni_proxy = self.sni_proxy.clone();
+        let connector = self.connector.clone();
+        let node = host_id.to_string();
+
+        tokio::spawn(async move {
+            while let Ok((mut inbound, _)) = listener.accept().await {
+                let sni_proxy = sni_proxy.clone();
+                let connector = connector.clone();
+                let node = node.clone();
+                tokio::spawn(async move {
+                    match sni_connect(&connector, &sni_proxy, &node).await {
+                        Ok(mut outbound) => {
+                            _ = copy_bidirectional(&mut inbound, &mut outbound).await;
+                        }
+                        Err(e) => log::warn!("astra: unable to reach node [{node}] through [{sni_proxy}]: {e}"),
+                    }
+                });
+            }
+        });
+
+        forwards.insert(host_id.to_string(), address);
+        Ok(address)
+    }
+}
+
+#[async_trait]
+impl AddressTranslator for Astra {
+    async fn translate_address(&self, untranslated_peer: &UntranslatedPeer) -> Result<SocketAddr, TranslationError> {
+        let host_id = untranslated_peer.host_id.to_string();
+        self.local_address(&host_id)
+            .await
+            .map_err(|e| {
+                log::warn!("astra: unable to forward node [{host_id}]: {e}");
+                TranslationError::NoRuleForAddress
+            })
+    }
+}
+
+/// Opens a TLS connection to the node `host_id` through the SNI proxy. The proxy certificate is checked
+/// against the bundle CA, but not against the name, which is the node and not the proxy.
+async fn sni_connect(connector: &SslConnector, sni_proxy: &str, host_id: &str) -> Result<SslStream<TcpStream>> {
+    let mut config = connector.configure()?;
+    config.set_verify_hostname(false);
+    let tcp = TcpStream::connect(sni_proxy).await?;
+    let mut stream = SslStream::new(config.into_ssl(host_id)?, tcp)?;
+    Pin::new(&mut stream).connect().await?;
+    Ok(stream)
+}
+
+/// The string value of the first `"key"` of a JSON document. Only the flat values of the bundle config
+/// and the metadata service replies are read, so escapes are not handled.
+fn json_string(json: &str, key: &str) -> Option<String> {
+    let value = json_value(json, key)?.strip_prefix('"')?;
+    Some(value[..value.find('"')?].to_string())
+}
+
+fn json_number(json: &str, key: &str) -> Option<u16> {
+    let value = json_value(json, key)?;
+    value[..value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len())].parse().ok()
+}
+
+/// The strings of the first `"key"` array of a JSON document.
+fn json_strings(json: &str, key: &str) -> Vec<String> {
+    let Some(array) = json_value(json, key).and_then(|value| value.strip_prefix('[')) else {
+        return vec![];
+    };
+
+    array[..array.find(']').unwrap_or(array.len())]
+        .split(',')
+        .map(|item| item.trim().trim_matches('"').to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// What follows `"key":` in a JSON document.
+fn json_value<'a>(json: &'a str, key: &str) -> Option<&'a str> {
+    let at = json.find(&format!("\"{key}\""))? + key.len() + 2;
+    Some(json[at..].trim_start().strip_prefix(':')?.trim_start())
+}
@@ -0,0 +1,310 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use openssl::pkey::PKey;
+use openssl::sha::sha256;
+use openssl::sign::Signer;
+use scylla::authentication::{AuthError, AuthenticatorProvider, AuthenticatorSession, PlainTextAuthenticator};
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+pub const MECHANISM_NONE: &str = "none";
+pub const MECHANISM_PASSWORD: &str = "password";
+pub const MECHANISM_COMMAND: &str = "command";
+pub const MECHANISM_SIGV4: &str = "sigv4";
+
+/// Everything an authentication mechanism may need to build its provider.
+/// Each mechanism only looks at the fields relevant to it.
+#[derive(Debug, Default, Clone)]
+pub struct AuthOptions {
+    pub mechanism: String,
+    pub user: String,
+    pub password: String,
+    pub command: String,
+    /// AWS region and credentials signing the SigV4 exchange.
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: String,
+}
+
+/// A way of authenticating against the cluster.
+///
+/// New mechanisms (Kerberos, corporate SSO, ...) are added by implementing this trait
+/// and registering the implementation in [`mechanisms`].
+pub trait Mechanism {
+    fn name(&self) -> &'static str;
+
+    fn provider(&self, options: &AuthOptions) -> Result<Option<Arc<dyn AuthenticatorProvider>>>;
+}
+
+struct NoAuth;
+
+impl Mechanism for NoAuth {
+    fn name(&self) -> &'static str {
+        MECHANISM_NONE
+    }
+
+    fn provider(&self, _options: &AuthOptions) -> Result<Option<Arc<dyn AuthenticatorProvider>>> {
+        Ok(None)
+    }
+}
+
+/// SASL PLAIN. Works with `PasswordAuthenticator` and the LDAP backed `SaslauthdAuthenticator`.
+struct Password;
+
+impl Mechanism for Password {
+    fn name(&self) -> &'static str {
+        MECHANISM_PASSWORD
+    }
+
+    fn provider(&self, options: &AuthOptions) -> Result<Option<Arc<dyn AuthenticatorProvider>>> {
+        if options.user.is_empty() {
+            return Err(anyhow::anyhow!("A user is required for [{MECHANISM_PASSWORD}] authentication"));
+        }
+
+        Ok(Some(Arc::new(PlainTextAuthenticator::new(
+            options.user.clone(),
+            options.password.clone(),
+        ))))
+    }
+}
+
+/// Delegates the SASL exchange to an external program, so custom authenticators
+/// can be plugged in without rebuilding the tool.
+///
+/// The program is called once per step with the step (`initial` or `challenge`) and the
+/// server's authenticator class name as arguments. The server token, if any, is written to
+/// its stdin and whatever it prints to stdout is sent back as the response.
+struct External;
+
+impl Mechanism for External {
+    fn name(&self) -> &'static str {
+        MECHANISM_COMMAND
+    }
+
+    fn provider(&self, options: &AuthOptions) -> Result<Option<Arc<dyn AuthenticatorProvider>>> {
+        if options.command.is_empty() {
+            return Err(anyhow::anyhow!("An auth command is required for [{MECHANISM_COMMAND}] authentication"));
+        }
+
+        Ok(Some(Arc::new(CommandAuthenticator { command: options.command.clone() })))
+    }
+}
+
+/// AWS Signature Version 4, the authentication of Amazon Keyspaces, which only accepts TLS connections.
+struct SigV4;
+
+impl Mechanism for SigV4 {
+    fn name(&self) -> &'static str {
+        MECHANISM_SIGV4
+    }
+
+    fn provider(&self, options: &AuthOptions) -> Result<Option<Arc<dyn AuthenticatorProvider>>> {
+        if options.region.is_empty() {
+            return Err(anyhow::anyhow!("A region is required for [{MECHANISM_SIGV4}] authentication"));
+        }
+        if options.access_key_id.is_empty() || options.secret_access_key.is_empty() {
+            return Err(anyhow::anyhow!("AWS credentials are required for [{MECHANISM_SIGV4}] authentication"));
+        }
+
+        Ok(Some(Arc::new(SigV4Authenticator { options: options.clone() })))
+    }
+}
+
+pub fn mechanisms() -> Vec<Box<dyn Mechanism>> {
+    vec![Box::new(NoAuth), Box::new(Password), Box::new(External), Box::new(SigV4)]
+}
+
+/// Resolves the authenticator provider for the configured mechanism.
+/// Without an explicit mechanism, password authentication is used when a user is set.
+pub fn provider(options: &AuthOptions) -> Result<Option<Arc<dyn AuthenticatorProvider>>> {
+    let name = if !options.mechanism.is_empty() {
+        options.mechanism.as_str()
+    } else if !options.user.is_empty() {
+        MECHANISM_PASSWORD
+    } else {
+        MECHANISM_NONE
+    };
+
+    let mechanism = mechanisms()
+        .into_iter()
+        .find(|m| m.name() == name)
+        .ok_or_else(|| {
+            let available: Vec<&str> = mechanisms().iter().map(|m| m.name()).collect();
+            anyhow::anyhow!("Unknown auth mechanism: [{name}]. Available: {available:?}")
+        })?;
+
+    mechanism.provider(options)
+}
+
+struct CommandAuthenticator {
+    command: String,
+}
+
+struct CommandAuthenticatorSession {
+    command: String,
+    authenticator_name: String,
+}
+
+async fn run_command(command: &str, step: &str, authenticator_name: &str, token: Option<&[u8]>) -> Result<Option<Vec<u8>>, AuthError> {
+    let mut child = Command::new(command)
+        .arg(step)
+        .arg(authenticator_name)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|e| format!("Unable to run auth command [{command}]: {e}"))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        if let Some(token) = token {
+            stdin.write_all(token)
+                .await
+                .map_err(|e| format!("Unable to pass token to auth command [{command}]: {e}"))?;
+        }
+    }
+
+    let output = child.wait_with_output()
+        .await
+        .map_err(|e| format!("Auth command [{command}] failed: {e}"))?;
+
+    if !output.status.success() {
+        return Err(format!("Auth command [{command}] exited with {}", output.status));
+    }
+
+    if output.stdout.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(output.stdout))
+}
+
+#[async_trait]
+impl AuthenticatorProvider for CommandAuthenticator {
+    async fn start_authentication_session(
+        &self,
+        authenticator_name: &str,
+    ) -> Result<(Option<Vec<u8>>, Box<dyn AuthenticatorSession>), AuthError> {
+        let response = run_command(&self.command, "initial", authenticator_name, None).await?;
+        let session = CommandAuthenticatorSession {
+            command: self.command.clone(),
+            authenticator_name: authenticator_name.to_string(),
+        };
+
+        Ok((response, Box::new(session)))
+    }
+}
+
+#[async_trait]
+impl AuthenticatorSession for CommandAuthenticatorSession {
+    async fn evaluate_challenge(&mut self, token: Option<&[u8]>) -> Result<Option<Vec<u8>>, AuthError> {
+        run_command(&self.command, "challenge", &self.authenticator_name, token).await
+    }
+
+    async fn success(&mut self, _token: Option<&[u8]>) -> Result<(), AuthError> {
+        Ok(())
+    }
+}
+
+/// Service the Amazon Keyspaces signatures are scoped to.
+const SIGV4_SERVICE: &str = "cassandra";
+
+/// How long a signature is valid, in seconds.
+const SIGV4_EXPIRES: u32 = 900;
+
+struct SigV4Authenticator {
+    options: AuthOptions,
+}
+
+struct SigV4AuthenticatorSession {
+    options: AuthOptions,
+}
+
+#[async_trait]
+impl AuthenticatorProvider for SigV4Authenticator {
+    async fn start_authentication_session(
+        &self,
+        _authenticator_name: &str,
+    ) -> Result<(Option<Vec<u8>>, Box<dyn AuthenticatorSession>), AuthError> {
+        let session = SigV4AuthenticatorSession { options: self.options.clone() };
+        Ok((Some(b"SigV4\0\0".to_vec()), Box::new(session)))
+    }
+}
+
+#[async_trait]
+impl AuthenticatorSession for SigV4AuthenticatorSession {
+    async fn evaluate_challenge(&mut self, token: Option<&[u8]>) -> Result<Option<Vec<u8>>, AuthError> {
+        let challenge = String::from_utf8_lossy(token.unwrap_or_default());
+        let nonce = challenge
+            .split(',')
+            .find_map(|part| part.strip_prefix("nonce="))
+            .ok_or_else(|| format!("No nonce in the SigV4 challenge: [{challenge}]"))?;
+
+        sigv4_response(&self.options, nonce, Utc::now()).map(|response| Some(response.into_bytes()))
+    }
+
+    async fn success(&mut self, _token: Option<&[u8]>) -> Result<(), AuthError> {
+        Ok(())
+    }
+}
+
+/// Signs the server `nonce` as a presigned `PUT /authenticate` request.
+fn sigv4_response(options: &AuthOptions, nonce: &str, now: DateTime<Utc>) -> Result<String, AuthError> {
+    let timestamp = now.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+    let scope = format!("{date}/{}/{SIGV4_SERVICE}/aws4_request", options.region);
+
+    let mut query = [
+        "X-Amz-Algorithm=AWS4-HMAC-SHA256".to_string(),
+        format!("X-Amz-Credential={}%2F{}", options.access_key_id, uri_encode(&scope)),
+        format!("X-Amz-Date={}", uri_encode(&timestamp)),
+        format!("X-Amz-Expires={SIGV4_EXPIRES}"),
+    ];
+    query.sort();
+    let canonical_request = format!(
+        "PUT\n/authenticate\n{}\nhost:{SIGV4_SERVICE}\n\nhost\n{}",
+        query.join("&"),
+        hex(&sha256(nonce.as_bytes())),
+    );
+    let string_to_sign = format!("AWS4-HMAC-SHA256\n{timestamp}\n{scope}\n{}", hex(&sha256(canonical_request.as_bytes())));
+
+    let mut key = format!("AWS4{}", options.secret_access_key).into_bytes();
+    for part in [date.as_str(), options.region.as_str(), SIGV4_SERVICE, "aws4_request"] {
+        key = hmac(&key, part.as_bytes())?;
+    }
+    let signature = hex(&hmac(&key, string_to_sign.as_bytes())?);
+
+    let mut response = format!("signature={signature},access_key={},amzdate={timestamp}", options.access_key_id);
+    if !options.session_token.is_empty() {
+        response.push_str(&format!(",session_token={}", options.session_token));
+    }
+    Ok(response)
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Result<Vec<u8>, AuthError> {
+    let sign = || -> Result<Vec<u8>, openssl::error::ErrorStack> {
+        let key = PKey::hmac(key)?;
+        let mut signer = Signer::new(openssl::hash::MessageDigest::sha256(), &key)?;
+        signer.update(data)?;
+        signer.sign_to_vec()
+    };
+    sign().map_err(|e| format!("Unable to sign the SigV4 challenge: {e}"))
+}
+
+/// Percent encodes everything but the unreserved characters, as SigV4 query values are.
+fn uri_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            b => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
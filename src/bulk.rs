@@ -0,0 +1,166 @@
+use crate::scan::RateLimiter;
+use anyhow::{Context, Result};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use scylla::frame::response::result::{ColumnType, CqlValue};
+use scylla::frame::value::{CqlTimestamp, CqlTimeuuid};
+use scylla::prepared_statement::PreparedStatement;
+use scylla::transport::errors::{DbError, QueryError};
+use scylla::Session;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+use crate::Cancelled;
+
+const MIN_BACKOFF: Duration = Duration::from_millis(100);
+pub(crate) const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// How heavy DML is executed.
+#[derive(Debug, Clone)]
+pub struct BulkOptions {
+    /// Statements in flight at once.
+    pub concurrency: usize,
+    /// Statements per second, across all of them. `None` is unlimited.
+    pub rate: Option<u32>,
+    /// Retries of a statement that timed out or hit an overloaded node, before the run fails.
+    pub max_retries: u32,
+}
+
+impl Default for BulkOptions {
+    fn default() -> Self {
+        BulkOptions { concurrency: 8, rate: None, max_retries: 5 }
+    }
+}
+
+/// Executes a prepared statement once per set of bound values, throttled. When the cluster pushes
+/// back with timeouts or overload errors, every worker pauses, with the pause growing exponentially
+/// until statements succeed again.
+pub(crate) struct Executor<'a> {
+    session: &'a Session,
+    options: BulkOptions,
+    limiter: Mutex<RateLimiter>,
+    pause: Mutex<Pause>,
+}
+
+#[derive(Default)]
+struct Pause {
+    until: Option<Instant>,
+    backoff: Option<Duration>,
+}
+
+impl<'a> Executor<'a> {
+    pub(crate) fn new(session: &'a Session, options: BulkOptions) -> Executor<'a> {
+        let limiter = Mutex::new(RateLimiter::new(options.rate));
+        Executor { session, options, limiter, pause: Mutex::new(Pause::default()) }
+    }
+
+    /// Executes `statement` for every row of values. Returns the number of statements executed.
+    pub(crate) async fn run(
+        &self,
+        statement: &PreparedStatement,
+        rows: impl IntoIterator<Item = Vec<Option<CqlValue>>>,
+        cancel: &CancellationToken,
+    ) -> Result<usize> {
+        stream::iter(rows)
+            .map(|values| self.execute(statement, values, cancel))
+            .buffer_unordered(self.options.concurrency.max(1))
+            .try_fold(0, |done, ()| async move { Ok(done + 1) })
+            .await
+    }
+
+    async fn execute(&self, statement: &PreparedStatement, values: Vec<Option<CqlValue>>, cancel: &CancellationToken) -> Result<()> {
+        let mut attempt = 0;
+
+        loop {
+            self.wait().await;
+            if cancel.is_cancelled() {
+                return Err(Cancelled.into());
+            }
+
+            match self.session.execute_unpaged(statement, &values).await {
+                Ok(_) => {
+                    self.pause.lock().await.backoff = None;
+                    return Ok(());
+                }
+                Err(e) if pushback(&e) && attempt < self.options.max_retries => {
+                    attempt += 1;
+                    let mut pause = self.pause.lock().await;
+                    let backoff = pause.backoff.map_or(MIN_BACKOFF, |b| (b * 2).min(MAX_BACKOFF));
+                    log::warn!("cluster pushing back ({e}), pausing {}ms", backoff.as_millis());
+                    pause.backoff = Some(backoff);
+                    pause.until = Some(Instant::now() + backoff);
+                }
+                Err(e) => return Err(e).context(format!("statement failed after {attempt} retries")),
+            }
+        }
+    }
+
+    async fn wait(&self) {
+        let until = self.pause.lock().await.until;
+        if let Some(until) = until {
+            tokio::time::sleep_until(until).await;
+        }
+        self.limiter.lock().await.wait().await;
+    }
+}
+
+/// Errors that mean the cluster is struggling to keep up, rather than the statement being wrong.
+pub(crate) fn pushback(error: &QueryError) -> bool {
+    match error {
+        QueryError::DbError(e, _) => matches!(
+            e,
+            DbError::WriteTimeout { .. }
+                | DbError::ReadTimeout { .. }
+                | DbError::Overloaded
+                | DbError::Unavailable { .. }
+                | DbError::RateLimitReached { .. }
+        ),
+        QueryError::RequestTimeout(_) | QueryError::TimeoutError => true,
+        _ => false,
+    }
+}
+
+/// Parses one line of a key source: comma separated values, one per bind marker of `statement`,
+/// typed after the markers. An empty value is `null`.
+pub(crate) fn parse_values(statement: &PreparedStatement, line: &str) -> Result<Vec<Option<CqlValue>>> {
+    let specs = statement.get_variable_col_specs();
+    let values: Vec<&str> = line.split(',').map(str::trim).collect();
+    if values.len() != specs.len() {
+        return Err(anyhow::anyhow!("Expected {} value(s), got {}: [{line}]", specs.len(), values.len()));
+    }
+
+    specs
+        .iter()
+        .zip(values)
+        .map(|(spec, value)| {
+            if value.is_empty() {
+                return Ok(None);
+            }
+            parse_value(&spec.typ, value)
+                .map(Some)
+                .with_context(|| format!("Invalid value for [{}]: [{value}]", spec.name))
+        })
+        .collect()
+}
+
+fn parse_value(typ: &ColumnType, value: &str) -> Result<CqlValue> {
+    Ok(match typ {
+        ColumnType::Text => CqlValue::Text(value.to_string()),
+        ColumnType::Ascii => CqlValue::Ascii(value.to_string()),
+        ColumnType::Boolean => CqlValue::Boolean(value.parse()?),
+        ColumnType::TinyInt => CqlValue::TinyInt(value.parse()?),
+        ColumnType::SmallInt => CqlValue::SmallInt(value.parse()?),
+        ColumnType::Int => CqlValue::Int(value.parse()?),
+        ColumnType::BigInt => CqlValue::BigInt(value.parse()?),
+        ColumnType::Float => CqlValue::Float(value.parse()?),
+        ColumnType::Double => CqlValue::Double(value.parse()?),
+        ColumnType::Uuid => CqlValue::Uuid(value.parse()?),
+        ColumnType::Timeuuid => CqlValue::Timeuuid(value.parse::<CqlTimeuuid>()?),
+        ColumnType::Timestamp => CqlValue::Timestamp(CqlTimestamp(match value.parse::<i64>() {
+            Ok(millis) => millis,
+            Err(_) => chrono::DateTime::parse_from_rfc3339(value)?.timestamp_millis(),
+        })),
+        other => return Err(anyhow::anyhow!("Unsupported key type: {other:?}")),
+    })
+}
@@ -0,0 +1,79 @@
+use crate::cql::{self, Action, Name, ObjectKind, Token};
+use std::fmt::{Display, Formatter};
+
+/// Suffix of the log table Scylla creates next to a table with CDC enabled.
+pub(crate) const LOG_SUFFIX: &str = "_scylla_cdc_log";
+
+/// What `up` does when a migration alters or drops a table with CDC enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CdcCheck {
+    Off,
+    Warn,
+    Fail,
+}
+
+impl CdcCheck {
+    pub fn parse(value: &str) -> anyhow::Result<CdcCheck> {
+        match value {
+            "off" => Ok(CdcCheck::Off),
+            "" | "warn" => Ok(CdcCheck::Warn),
+            "fail" => Ok(CdcCheck::Fail),
+            _ => Err(anyhow::anyhow!("Unknown CDC check: [{value}]. Use off, warn or fail")),
+        }
+    }
+}
+
+/// A statement of a pending migration touching a table whose changes are streamed through CDC.
+#[derive(Debug, Clone)]
+pub struct CdcWarning {
+    pub migration: String,
+    pub summary: String,
+    pub table: Name,
+}
+
+impl Display for CdcWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}] {}: {} has CDC enabled, consumers of {}{LOG_SUFFIX} are affected",
+            self.migration, self.summary, self.table, self.table
+        )
+    }
+}
+
+/// The base table of a CDC log table, if `name` is one.
+pub(crate) fn base_table(name: &Name) -> Option<Name> {
+    let base = name.name.strip_suffix(LOG_SUFFIX)?;
+    Some(Name { keyspace: name.keyspace.clone(), name: base.to_string() })
+}
+
+/// `ALTER TABLE` and `DROP TABLE` statements of `up` on one of `cdc_tables`.
+pub(crate) fn check(migration: &str, up: &str, cdc_tables: &[Name]) -> Vec<CdcWarning> {
+    cql::parse_all(up)
+        .into_iter()
+        .filter(|s| matches!(s.action, Action::Alter | Action::Drop) && s.kind == Some(ObjectKind::Table))
+        .filter_map(|statement| {
+            let table = statement.name.clone()?;
+            cdc_tables.contains(&table).then(|| CdcWarning { migration: migration.to_string(), summary: statement.summary(), table })
+        })
+        .collect()
+}
+
+/// The value of `enabled` in the `cdc = {...}` option of a table definition, if given.
+pub(crate) fn enabled(definition: &str) -> Option<bool> {
+    let tokens = cql::tokenize(definition);
+    let start = tokens.windows(2).position(|w| matches!(&w[0], Token::Word(word) if word.eq_ignore_ascii_case("cdc")) && w[1] == Token::Symbol('='))?;
+
+    let mut options = tokens[start + 2..].iter().take_while(|t| **t != Token::Symbol('}'));
+    while let Some(token) = options.next() {
+        if *token == Token::Literal("enabled".to_string()) {
+            options.next();
+            return match options.next()? {
+                Token::Word(value) | Token::Literal(value) => Some(value.eq_ignore_ascii_case("true")),
+                _ => None,
+            };
+        }
+    }
+
+    None
+}
@@ -0,0 +1,94 @@
+use std::fmt::{Display, Formatter};
+
+/// Something about the tracker and the local migrations that `up` won't guess its way around.
+#[derive(Debug, Clone)]
+pub enum Conflict {
+    /// Pending, but older than a migration that is already applied.
+    OutOfOrder { migration: String, latest_applied: String },
+    /// Applied according to the tracker, but missing locally.
+    Orphan { migration: String },
+}
+
+impl Conflict {
+    pub fn migration(&self) -> &str {
+        match self {
+            Conflict::OutOfOrder { migration, .. } | Conflict::Orphan { migration } => migration,
+        }
+    }
+
+    /// Resolutions that make sense for this conflict.
+    pub fn options(&self) -> &'static [Resolution] {
+        match self {
+            Conflict::OutOfOrder { .. } => &[Resolution::Apply, Resolution::Skip, Resolution::MarkApplied, Resolution::Abort],
+            Conflict::Orphan { .. } => &[Resolution::Skip, Resolution::Forget, Resolution::Abort],
+        }
+    }
+}
+
+impl Display for Conflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Conflict::OutOfOrder { migration, latest_applied } => {
+                write!(f, "out of order: [{migration}] is pending but [{latest_applied}] is already applied")
+            }
+            Conflict::Orphan { migration } => write!(f, "orphan: [{migration}] is applied but missing locally"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// Apply the migration in this run anyway.
+    Apply,
+    /// Leave it alone for this run.
+    Skip,
+    /// Record the migration as applied without executing it.
+    MarkApplied,
+    /// Remove the migration from the tracker.
+    Forget,
+    /// Stop the run.
+    Abort,
+}
+
+impl Display for Resolution {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Resolution::Apply => "apply",
+            Resolution::Skip => "skip",
+            Resolution::MarkApplied => "mark applied",
+            Resolution::Forget => "forget",
+            Resolution::Abort => "abort",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// Decides what to do about each conflict found by `up`. Without a resolver, conflicts fail the run.
+pub trait ConflictResolver: Send + Sync {
+    /// Must return one of `conflict.options()`.
+    fn resolve(&self, conflict: &Conflict) -> Resolution;
+}
+
+pub(crate) fn detect(local: &[String], applied: &[String]) -> Vec<Conflict> {
+    let mut conflicts = vec![];
+
+    if let Some(latest_applied) = applied.iter().max() {
+        for migration in local {
+            if !applied.contains(migration) && migration < latest_applied {
+                conflicts.push(Conflict::OutOfOrder {
+                    migration: migration.clone(),
+                    latest_applied: latest_applied.clone(),
+                });
+            }
+        }
+    }
+
+    for migration in applied {
+        if !local.contains(migration) {
+            conflicts.push(Conflict::Orphan { migration: migration.clone() });
+        }
+    }
+
+    conflicts
+}
@@ -0,0 +1,723 @@
+use std::fmt::{Display, Formatter};
+
+/// Splits the contents of a migration file into the statements to execute, at the semicolons outside string
+/// literals, quoted identifiers, `$$` bodies and comments. A `BEGIN [UNLOGGED | COUNTER] BATCH ... APPLY BATCH`
+/// stays one statement, semicolons between its statements included. Pieces holding nothing but whitespace and
+/// comments are dropped.
+pub fn split(query: &str) -> Vec<&str> {
+    // unable to pass queries in a single request.
+    // batch request doesn't accept create table queries.
+    // so splitting for now
+    let bytes = query.as_bytes();
+    let mut pieces = vec![];
+    let mut start = 0;
+    let mut i = 0;
+
+    // the delimiters are all ASCII, so every slice boundary is a char boundary
+    while i < bytes.len() {
+        match (bytes[i], bytes.get(i + 1).copied()) {
+            (b'-', Some(b'-')) | (b'/', Some(b'/')) => i = find(bytes, i + 2, b"\n").map_or(bytes.len(), |at| at + 1),
+            (b'/', Some(b'*')) => i = find(bytes, i + 2, b"*/").map_or(bytes.len(), |at| at + 2),
+            (b'$', Some(b'$')) => i = find(bytes, i + 2, b"$$").map_or(bytes.len(), |at| at + 2),
+            (b'\'' | b'"', _) => i = skip_quoted(bytes, i),
+            (b';', _) => {
+                pieces.push((start, i));
+                i += 1;
+                start = i;
+            }
+            _ => i += 1,
+        }
+    }
+    pieces.push((start.min(query.len()), query.len()));
+
+    let mut statements = vec![];
+    // start of the batch the pieces are part of, until its APPLY BATCH
+    let mut batch: Option<usize> = None;
+    let mut last_end = 0;
+    for (start, end) in pieces {
+        let tokens = tokenize(&query[start..end]);
+        if tokens.is_empty() {
+            continue;
+        }
+        last_end = end;
+        match batch {
+            Some(batch_start) if ends_batch(&tokens) => {
+                statements.push(&query[batch_start..end]);
+                batch = None;
+            }
+            Some(_) => {}
+            None if begins_batch(&tokens) && !ends_batch(&tokens) => batch = Some(start),
+            None => statements.push(&query[start..end]),
+        }
+    }
+    // an unterminated batch is left for the server to reject
+    if let Some(batch_start) = batch {
+        statements.push(&query[batch_start..last_end]);
+    }
+
+    statements
+}
+
+fn begins_batch(tokens: &[Token]) -> bool {
+    let words: Vec<&str> = tokens.iter().take(3).map(|token| match token {
+        Token::Word(word) => word.as_str(),
+        _ => "",
+    }).collect();
+    match words.as_slice() {
+        [begin, batch, ..] if begin.eq_ignore_ascii_case("BEGIN") && batch.eq_ignore_ascii_case("BATCH") => true,
+        [begin, kind, batch] => {
+            begin.eq_ignore_ascii_case("BEGIN")
+                && (kind.eq_ignore_ascii_case("UNLOGGED") || kind.eq_ignore_ascii_case("COUNTER"))
+                && batch.eq_ignore_ascii_case("BATCH")
+        }
+        _ => false,
+    }
+}
+
+fn ends_batch(tokens: &[Token]) -> bool {
+    matches!(
+        tokens,
+        [.., Token::Word(apply), Token::Word(batch)] if apply.eq_ignore_ascii_case("APPLY") && batch.eq_ignore_ascii_case("BATCH")
+    )
+}
+
+/// `statement` without its `--`, `//` and `/* */` comments, which are documentation for the authors, not CQL to
+/// send. Line comments keep their newline, block comments become a space, and literals are left untouched.
+pub fn strip_comments(statement: &str) -> String {
+    let bytes = statement.as_bytes();
+    let mut out = String::with_capacity(statement.len());
+    let mut start = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let end = match (bytes[i], bytes.get(i + 1).copied()) {
+            (b'-', Some(b'-')) | (b'/', Some(b'/')) => find(bytes, i + 2, b"\n").unwrap_or(bytes.len()),
+            (b'/', Some(b'*')) => find(bytes, i + 2, b"*/").map_or(bytes.len(), |at| at + 2),
+            (b'$', Some(b'$')) => {
+                i = find(bytes, i + 2, b"$$").map_or(bytes.len(), |at| at + 2);
+                continue;
+            }
+            (b'\'' | b'"', _) => {
+                i = skip_quoted(bytes, i);
+                continue;
+            }
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+
+        out.push_str(&statement[start..i]);
+        if bytes[i] == b'/' && bytes[i + 1] == b'*' {
+            out.push(' ');
+        }
+        i = end;
+        start = end;
+    }
+    out.push_str(&statement[start.min(statement.len())..]);
+
+    out.trim().to_string()
+}
+
+/// Index right after the string literal or quoted identifier starting at `start`. A doubled quote is an escaped quote.
+fn skip_quoted(bytes: &[u8], start: usize) -> usize {
+    let quote = bytes[start];
+    let mut i = start + 1;
+    while i < bytes.len() {
+        if bytes[i] == quote {
+            if bytes.get(i + 1) == Some(&quote) {
+                i += 2;
+                continue;
+            }
+            break;
+        }
+        i += 1;
+    }
+
+    i + 1
+}
+
+/// Index of the first `needle` in `bytes` at or after `from`.
+fn find(bytes: &[u8], from: usize, needle: &[u8]) -> Option<usize> {
+    bytes.get(from..)?.windows(needle.len()).position(|window| window == needle).map(|at| from + at)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Create,
+    Alter,
+    Drop,
+    Truncate,
+    Insert,
+    Update,
+    Delete,
+    Select,
+    Use,
+    Grant,
+    Revoke,
+    Batch,
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ObjectKind {
+    Keyspace,
+    Table,
+    Type,
+    Index,
+    MaterializedView,
+    Function,
+    Aggregate,
+    Role,
+    Trigger,
+}
+
+impl Display for ObjectKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ObjectKind::Keyspace => "keyspace",
+            ObjectKind::Table => "table",
+            ObjectKind::Type => "type",
+            ObjectKind::Index => "index",
+            ObjectKind::MaterializedView => "materialized view",
+            ObjectKind::Function => "function",
+            ObjectKind::Aggregate => "aggregate",
+            ObjectKind::Role => "role",
+            ObjectKind::Trigger => "trigger",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Risk {
+    Low,
+    Medium,
+    High,
+}
+
+impl Display for Risk {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Risk::Low => "low",
+            Risk::Medium => "medium",
+            Risk::High => "high",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// Keywords that can't be used as identifiers unless double quoted.
+pub(crate) const RESERVED: [&str; 58] = [
+    "ADD", "ALLOW", "ALTER", "AND", "APPLY", "ASC", "AUTHORIZE", "BATCH", "BEGIN", "BY", "COLUMNFAMILY", "CREATE", "DELETE",
+    "DESC", "DESCRIBE", "DROP", "ENTRIES", "EXECUTE", "FROM", "FULL", "GRANT", "IF", "IN", "INDEX", "INFINITY", "INSERT",
+    "INTO", "IS", "KEYSPACE", "LIMIT", "MATERIALIZED", "MODIFY", "NAN", "NORECURSIVE", "NOT", "NULL", "OF", "ON", "OR",
+    "ORDER", "PRIMARY", "RENAME", "REPLACE", "REVOKE", "SCHEMA", "SELECT", "SET", "TABLE", "TO", "TOKEN", "TRUNCATE",
+    "UNLOGGED", "UPDATE", "USE", "USING", "VIEW", "WHERE", "WITH",
+];
+
+pub(crate) fn is_reserved(word: &str) -> bool {
+    RESERVED.iter().any(|reserved| reserved.eq_ignore_ascii_case(word))
+}
+
+/// Double quotes an identifier that would otherwise be lowercased or rejected.
+pub(crate) fn quote(identifier: &str) -> String {
+    let plain = identifier.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if plain && !is_reserved(identifier) && !identifier.starts_with(|c: char| c.is_ascii_digit()) {
+        identifier.to_string()
+    } else {
+        format!("\"{}\"", identifier.replace('"', "\"\""))
+    }
+}
+
+/// A keyspace qualified object name. Unquoted identifiers are lowercased, like the cluster does.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Name {
+    pub keyspace: Option<String>,
+    pub name: String,
+}
+
+impl Name {
+    /// The name as CQL, quoting the parts that need it.
+    pub(crate) fn quoted(&self) -> String {
+        match &self.keyspace {
+            Some(keyspace) => format!("{}.{}", quote(keyspace), quote(&self.name)),
+            None => quote(&self.name),
+        }
+    }
+}
+
+impl Display for Name {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.keyspace {
+            Some(keyspace) => write!(f, "{keyspace}.{}", self.name),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// What a single statement does, as far as it can be told without a full CQL grammar.
+#[derive(Debug, Clone)]
+pub struct Statement {
+    pub text: String,
+    pub action: Action,
+    pub kind: Option<ObjectKind>,
+    /// Object the statement targets. For data statements this is the table.
+    pub name: Option<Name>,
+    /// `IF EXISTS` or `IF NOT EXISTS` was given.
+    pub guarded: bool,
+    /// First keyword after the name of an `ALTER`, e.g. `ADD`, `DROP`, `RENAME` or `WITH`.
+    pub alteration: Option<String>,
+    /// Table an index, materialized view or trigger is built on.
+    pub base: Option<Name>,
+}
+
+impl Statement {
+    pub fn keyspace(&self) -> Option<&str> {
+        if self.kind == Some(ObjectKind::Keyspace) || self.action == Action::Use {
+            return self.name.as_ref().map(|n| n.name.as_str());
+        }
+
+        self.name
+            .as_ref()
+            .and_then(|n| n.keyspace.as_deref())
+            .or_else(|| self.base.as_ref().and_then(|b| b.keyspace.as_deref()))
+    }
+
+    pub fn risk(&self) -> Risk {
+        match self.action {
+            Action::Drop | Action::Truncate | Action::Delete => Risk::High,
+            Action::Alter => match self.alteration.as_deref() {
+                Some("DROP") | Some("RENAME") => Risk::High,
+                _ => Risk::Medium,
+            },
+            Action::Create => match self.kind {
+                // built from the existing data of the base table
+                Some(ObjectKind::Index) | Some(ObjectKind::MaterializedView) => Risk::Medium,
+                _ => Risk::Low,
+            },
+            Action::Select | Action::Use => Risk::Low,
+            _ => Risk::Medium,
+        }
+    }
+
+    /// Short human description, e.g. `DROP table app.users`.
+    pub fn summary(&self) -> String {
+        let action = format!("{:?}", self.action).to_uppercase();
+        let mut out = match (self.kind, &self.name) {
+            (Some(kind), Some(name)) => format!("{action} {kind} {name}"),
+            (Some(kind), None) => format!("{action} {kind}"),
+            (None, Some(name)) => format!("{action} {name}"),
+            (None, None) => action,
+        };
+
+        if let Some(alteration) = &self.alteration {
+            out.push_str(&format!(" ({alteration})"));
+        }
+
+        if let Some(base) = &self.base {
+            out.push_str(&format!(" on {base}"));
+        }
+
+        out
+    }
+}
+
+/// Parses every statement of a migration file. Unqualified names are resolved against
+/// the keyspace of a preceding `USE`.
+pub fn parse_all(query: &str) -> Vec<Statement> {
+    let mut keyspace: Option<String> = None;
+
+    split(query)
+        .into_iter()
+        .filter(|s| !tokenize(s).is_empty())
+        .map(|s| {
+            let mut statement = parse(s);
+            if statement.action == Action::Use {
+                keyspace = statement.name.as_ref().map(|n| n.name.clone());
+            } else if statement.kind != Some(ObjectKind::Keyspace) {
+                for name in [&mut statement.name, &mut statement.base].into_iter().flatten() {
+                    if name.keyspace.is_none() {
+                        name.keyspace = keyspace.clone();
+                    }
+                }
+            }
+            statement
+        })
+        .collect()
+}
+
+pub fn parse(text: &str) -> Statement {
+    let tokens = tokenize(text);
+    let mut parser = Parser { tokens: &tokens, pos: 0 };
+
+    let mut statement = Statement {
+        text: text.trim().to_string(),
+        action: Action::Other,
+        kind: None,
+        name: None,
+        guarded: false,
+        alteration: None,
+        base: None,
+    };
+
+    let Some(first) = parser.keyword() else {
+        return statement;
+    };
+
+    match first.as_str() {
+        "CREATE" => {
+            statement.action = Action::Create;
+            parser.accept_all(&["OR", "REPLACE"]);
+            parser.accept("CUSTOM");
+            statement.kind = parser.object_kind();
+            statement.guarded = parser.accept_all(&["IF", "NOT", "EXISTS"]);
+
+            match statement.kind {
+                Some(ObjectKind::Index) | Some(ObjectKind::Trigger) => {
+                    if !parser.peek_keyword("ON") {
+                        statement.name = parser.name();
+                    }
+                    if parser.accept("ON") {
+                        statement.base = parser.name();
+                    }
+                }
+                Some(ObjectKind::MaterializedView) => {
+                    statement.name = parser.name();
+                    if parser.skip_to("FROM") {
+                        statement.base = parser.name();
+                    }
+                }
+                _ => statement.name = parser.name(),
+            }
+        }
+        "ALTER" => {
+            statement.action = Action::Alter;
+            statement.kind = parser.object_kind();
+            statement.guarded = parser.accept_all(&["IF", "EXISTS"]);
+            statement.name = parser.name();
+            statement.alteration = parser.keyword();
+        }
+        "DROP" => {
+            statement.action = Action::Drop;
+            statement.kind = parser.object_kind();
+            statement.guarded = parser.accept_all(&["IF", "EXISTS"]);
+            statement.name = parser.name();
+        }
+        "TRUNCATE" => {
+            statement.action = Action::Truncate;
+            statement.kind = Some(ObjectKind::Table);
+            if !parser.accept("TABLE") {
+                parser.accept("COLUMNFAMILY");
+            }
+            statement.name = parser.name();
+        }
+        "INSERT" => {
+            statement.action = Action::Insert;
+            parser.accept("INTO");
+            statement.name = parser.name();
+        }
+        "UPDATE" => {
+            statement.action = Action::Update;
+            statement.name = parser.name();
+        }
+        "DELETE" | "SELECT" => {
+            statement.action = if first == "DELETE" { Action::Delete } else { Action::Select };
+            if parser.skip_to("FROM") {
+                statement.name = parser.name();
+            }
+        }
+        "USE" => {
+            statement.action = Action::Use;
+            statement.name = parser.name();
+        }
+        "GRANT" => statement.action = Action::Grant,
+        "REVOKE" => statement.action = Action::Revoke,
+        "BEGIN" => statement.action = Action::Batch,
+        _ => {}
+    }
+
+    statement
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Token {
+    /// Unquoted identifier or keyword.
+    Word(String),
+    /// Double quoted identifier, without the quotes.
+    Quoted(String),
+    /// String literal or `$$` body, without the delimiters.
+    Literal(String),
+    Symbol(char),
+}
+
+/// Splits a statement into tokens, dropping whitespace and comments.
+pub(crate) fn tokenize(text: &str) -> Vec<Token> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+
+        if c.is_whitespace() {
+            i += 1;
+        } else if (c == '-' && next == Some('-')) || (c == '/' && next == Some('/')) {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && next == Some('*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                i += 1;
+            }
+            i += 2;
+        } else if c == '$' && next == Some('$') {
+            let start = i + 2;
+            i = start;
+            while i < chars.len() && !(chars[i] == '$' && chars.get(i + 1) == Some(&'$')) {
+                i += 1;
+            }
+            tokens.push(Token::Literal(chars[start..i.min(chars.len())].iter().collect()));
+            i += 2;
+        } else if c == '\'' || c == '"' {
+            let mut value = String::new();
+            i += 1;
+            while i < chars.len() {
+                if chars[i] == c {
+                    // a doubled quote is an escaped quote
+                    if chars.get(i + 1) == Some(&c) {
+                        value.push(c);
+                        i += 2;
+                        continue;
+                    }
+                    break;
+                }
+                value.push(chars[i]);
+                i += 1;
+            }
+            i += 1;
+            tokens.push(if c == '"' { Token::Quoted(value) } else { Token::Literal(value) });
+        } else if c.is_alphanumeric() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Word(chars[start..i].iter().collect()));
+        } else {
+            tokens.push(Token::Symbol(c));
+            i += 1;
+        }
+    }
+
+    tokens
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.tokens.get(self.pos), Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    fn keyword(&mut self) -> Option<String> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Word(w)) => {
+                self.pos += 1;
+                Some(w.to_uppercase())
+            }
+            _ => None,
+        }
+    }
+
+    fn accept(&mut self, keyword: &str) -> bool {
+        if self.peek_keyword(keyword) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    /// Consumes the whole keyword sequence, or nothing.
+    fn accept_all(&mut self, keywords: &[&str]) -> bool {
+        let start = self.pos;
+        for keyword in keywords {
+            if !self.accept(keyword) {
+                self.pos = start;
+                return false;
+            }
+        }
+        true
+    }
+
+    fn skip_to(&mut self, keyword: &str) -> bool {
+        while self.pos < self.tokens.len() {
+            if self.accept(keyword) {
+                return true;
+            }
+            self.pos += 1;
+        }
+        false
+    }
+
+    fn object_kind(&mut self) -> Option<ObjectKind> {
+        let kind = match self.keyword()?.as_str() {
+            "KEYSPACE" | "SCHEMA" => ObjectKind::Keyspace,
+            "TABLE" | "COLUMNFAMILY" => ObjectKind::Table,
+            "TYPE" => ObjectKind::Type,
+            "INDEX" => ObjectKind::Index,
+            "MATERIALIZED" => {
+                self.accept("VIEW");
+                ObjectKind::MaterializedView
+            }
+            "FUNCTION" => ObjectKind::Function,
+            "AGGREGATE" => ObjectKind::Aggregate,
+            "ROLE" | "USER" => ObjectKind::Role,
+            "TRIGGER" => ObjectKind::Trigger,
+            _ => {
+                self.pos -= 1;
+                return None;
+            }
+        };
+
+        Some(kind)
+    }
+
+    fn identifier(&mut self) -> Option<String> {
+        let identifier = match self.tokens.get(self.pos)? {
+            Token::Word(w) => w.to_lowercase(),
+            Token::Quoted(q) => q.clone(),
+            _ => return None,
+        };
+        self.pos += 1;
+        Some(identifier)
+    }
+
+    fn name(&mut self) -> Option<Name> {
+        let first = self.identifier()?;
+        if self.tokens.get(self.pos) == Some(&Token::Symbol('.')) {
+            self.pos += 1;
+            if let Some(second) = self.identifier() {
+                return Some(Name { keyspace: Some(first), name: second });
+            }
+        }
+
+        Some(Name { keyspace: None, name: first })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_at_semicolons() {
+        assert_eq!(split("CREATE TABLE a (id int PRIMARY KEY);\nDROP TABLE b;\n"), vec!["CREATE TABLE a (id int PRIMARY KEY)", "\nDROP TABLE b"]);
+    }
+
+    #[test]
+    fn split_drops_empty_pieces() {
+        assert_eq!(split("-- only a comment\n;;\n/* and another */;"), Vec::<&str>::new());
+        assert_eq!(split("DROP TABLE a;\n-- trailing comment\n").len(), 1);
+    }
+
+    #[test]
+    fn split_keeps_semicolons_in_literals() {
+        assert_eq!(split("INSERT INTO a (v) VALUES ('it''s; fine');"), vec!["INSERT INTO a (v) VALUES ('it''s; fine')"]);
+        assert_eq!(split("SELECT \"a;b\" FROM t;"), vec!["SELECT \"a;b\" FROM t"]);
+    }
+
+    #[test]
+    fn split_keeps_semicolons_in_dollar_bodies() {
+        let query = "CREATE FUNCTION f (x int) RETURNS NULL ON NULL INPUT RETURNS int LANGUAGE lua AS $$ a = 1; return x; $$;\nDROP TABLE t;";
+        let statements = split(query);
+        assert_eq!(statements.len(), 2);
+        assert!(statements[0].ends_with("return x; $$"));
+    }
+
+    #[test]
+    fn split_ignores_comment_markers_in_literals() {
+        assert_eq!(split("INSERT INTO a (v) VALUES ('-- not a comment; really');"), vec!["INSERT INTO a (v) VALUES ('-- not a comment; really')"]);
+    }
+
+    #[test]
+    fn split_ignores_semicolons_in_comments() {
+        assert_eq!(split("DROP TABLE a; -- one; two\nDROP TABLE b; /* three; */"), vec!["DROP TABLE a", " -- one; two\nDROP TABLE b"]);
+    }
+
+    #[test]
+    fn split_unterminated() {
+        assert_eq!(split("DROP TABLE a; INSERT INTO b (v) VALUES ('open; quote"), vec!["DROP TABLE a", " INSERT INTO b (v) VALUES ('open; quote"]);
+        assert_eq!(split("DROP TABLE a; /* open; comment"), vec!["DROP TABLE a"]);
+        assert_eq!(split("DROP TABLE a; SELECT $$ open; body"), vec!["DROP TABLE a", " SELECT $$ open; body"]);
+    }
+
+    #[test]
+    fn split_keeps_batches_whole() {
+        let query = "BEGIN BATCH\nINSERT INTO a (id) VALUES (1);\nINSERT INTO a (id) VALUES (2);\nAPPLY BATCH;\nDROP TABLE b;";
+        assert_eq!(
+            split(query),
+            vec!["BEGIN BATCH\nINSERT INTO a (id) VALUES (1);\nINSERT INTO a (id) VALUES (2);\nAPPLY BATCH", "\nDROP TABLE b"]
+        );
+        let query = "begin unlogged batch INSERT INTO a (id) VALUES (1); UPDATE a SET v = 1 WHERE id = 2; apply batch;";
+        assert_eq!(split(query).len(), 1);
+        let query = "BEGIN COUNTER BATCH UPDATE c SET n = n + 1 WHERE id = 1; APPLY BATCH";
+        assert_eq!(split(query).len(), 1);
+        assert_eq!(split("BEGIN BATCH INSERT INTO a (id) VALUES (1) APPLY BATCH; DROP TABLE b;").len(), 2);
+    }
+
+    #[test]
+    fn split_unterminated_batch() {
+        assert_eq!(split("BEGIN BATCH INSERT INTO a (id) VALUES (1); INSERT INTO a (id) VALUES (2);\n-- end\n"), vec![
+            "BEGIN BATCH INSERT INTO a (id) VALUES (1); INSERT INTO a (id) VALUES (2)"
+        ]);
+    }
+
+    #[test]
+    fn strip_comments_of_every_kind() {
+        assert_eq!(strip_comments("-- heading\nDROP TABLE a // why\n"), "DROP TABLE a");
+        assert_eq!(strip_comments("DROP/* inline */TABLE a"), "DROP TABLE a");
+    }
+
+    #[test]
+    fn strip_comments_keeps_literals() {
+        assert_eq!(strip_comments("INSERT INTO a (v) VALUES ('-- kept /* too */') -- dropped"), "INSERT INTO a (v) VALUES ('-- kept /* too */')");
+        assert_eq!(strip_comments("INSERT INTO a (v) VALUES ('it''s') -- dropped"), "INSERT INTO a (v) VALUES ('it''s')");
+        assert_eq!(strip_comments("AS $$ -- kept $$"), "AS $$ -- kept $$");
+    }
+
+    #[test]
+    fn strip_comments_unterminated() {
+        assert_eq!(strip_comments("DROP TABLE a /* open"), "DROP TABLE a");
+        assert_eq!(strip_comments("SELECT 'open -- quote"), "SELECT 'open -- quote");
+    }
+
+    #[test]
+    fn tokenize_literals() {
+        assert_eq!(tokenize("'it''s'"), vec![Token::Literal("it's".to_string())]);
+        assert_eq!(tokenize("\"My \"\"Table\"\"\""), vec![Token::Quoted("My \"Table\"".to_string())]);
+        assert_eq!(tokenize("$$ a; b $$"), vec![Token::Literal(" a; b ".to_string())]);
+    }
+
+    #[test]
+    fn tokenize_skips_comments() {
+        assert_eq!(
+            tokenize("DROP -- gone\n/* gone */ a // gone"),
+            vec![Token::Word("DROP".to_string()), Token::Word("a".to_string())]
+        );
+        assert_eq!(tokenize("SELECT '-- kept'"), vec![Token::Word("SELECT".to_string()), Token::Literal("-- kept".to_string())]);
+    }
+
+    #[test]
+    fn tokenize_unterminated() {
+        assert_eq!(tokenize("'open"), vec![Token::Literal("open".to_string())]);
+        assert_eq!(tokenize("a /* open"), vec![Token::Word("a".to_string())]);
+        assert_eq!(tokenize("$$ open"), vec![Token::Literal(" open".to_string())]);
+    }
+}
@@ -1,108 +1,1466 @@
 use chrono::Utc;
-use scylla::{FromRow, IntoTypedRows, Session, SessionBuilder};
-use crate::PARTITION_KEY;
+use scylla::authentication::AuthenticatorProvider;
+use openssl::ssl::SslContext;
+use scylla::frame::response::result::CqlValue;
+use scylla::query::Query;
+use scylla::statement::{Consistency, SerialConsistency};
+use scylla::transport::load_balancing::DefaultPolicy;
+use scylla::transport::session::PoolSize;
+use scylla::transport::errors::QueryError;
+use scylla::transport::Compression;
+use scylla::transport::topology::Strategy;
+use scylla::{ExecutionProfile, FromRow, IntoTypedRows, Session, SessionBuilder};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use uuid::Uuid;
+use crate::astra::{self, Astra};
+use crate::cdc;
+use crate::cql::{Name, ObjectKind};
+use crate::proxy::{Proxy, ProxyOptions};
+use crate::stats::TableStats;
 
-#[derive(Debug, FromRow)]
-struct MigrationData {
-    id: String,
-    status: String,
+/// Keyspace of the tracker tables, unless configured otherwise.
+pub const DEFAULT_KEYSPACE: &str = "scylladb_migrate_ks";
+/// Table of the migration history, unless configured otherwise.
+pub const DEFAULT_TABLE: &str = "migrations";
+/// Partition of the migration history in its table, unless configured otherwise.
+pub const DEFAULT_PARTITION: &str = "migrate";
+
+/// Where the migration history is kept.
+#[derive(Debug, Clone)]
+pub struct Tracker {
+    /// E.g. one per product sharing a cluster, so their histories stay independent.
+    pub keyspace: String,
+    pub table: String,
+    /// Value of the `type` partition key of the history rows. Services sharing a table keep separate histories with
+    /// one each.
+    pub partition: String,
 }
 
-pub(crate) async fn session(db_url: &str) -> anyhow::Result<Session> {
-    let session = SessionBuilder::new()
-        .known_node(db_url)
-        .build()
-        .await?;
+impl Default for Tracker {
+    fn default() -> Self {
+        Tracker {
+            keyspace: DEFAULT_KEYSPACE.to_string(),
+            table: DEFAULT_TABLE.to_string(),
+            partition: DEFAULT_PARTITION.to_string(),
+        }
+    }
+}
+
+static TRACKER: OnceLock<Tracker> = OnceLock::new();
+
+/// Configures the tracker for the whole process, before connecting. Process wide rather than a connect option,
+/// since lint and snapshots also tell the tracker keyspace apart from the application ones.
+pub fn set_tracker(mut tracker: Tracker) -> anyhow::Result<()> {
+    // unquoted names are case insensitive, and the schema metadata holds them in lowercase
+    tracker.keyspace = tracker.keyspace.to_lowercase();
+    tracker.table = tracker.table.to_lowercase();
+    for (what, name) in [("keyspace", &tracker.keyspace), ("table", &tracker.table)] {
+        if !is_identifier(name) {
+            return Err(anyhow::anyhow!("Invalid tracker {what}: [{name}]. Use up to 48 letters, digits and underscores"));
+        }
+    }
+    if tracker.table == SCAN_PROGRESS {
+        return Err(anyhow::anyhow!("The tracker table can't be named [{SCAN_PROGRESS}], which keeps the scan progress"));
+    }
+    if tracker.partition.is_empty() {
+        return Err(anyhow::anyhow!("The tracker partition can't be empty"));
+    }
+
+    TRACKER.set(tracker).map_err(|_| anyhow::anyhow!("The tracker is already configured"))
+}
+
+pub(crate) fn tracker() -> &'static Tracker {
+    TRACKER.get_or_init(Tracker::default)
+}
+
+/// Name of the tracker keyspace.
+pub(crate) fn keyspace() -> &'static str {
+    &tracker().keyspace
+}
+
+/// Partition of the migration history.
+pub(crate) fn partition() -> &'static str {
+    &tracker().partition
+}
+
+/// `keyspace.table` of the migration history.
+fn migrations_table() -> String {
+    format!("{}.{}", keyspace(), tracker().table)
+}
+
+/// Table of the backfill and anonymize progress, next to the history.
+const SCAN_PROGRESS: &str = "scan_progress";
+
+/// Table of the locks keeping concurrent runs from applying or reverting migrations together, next to the history.
+const MIGRATION_LOCK: &str = "migration_lock";
+
+/// `direction` of the tracker rows of up.cql and down.cql runs.
+const DIRECTION_UP: &str = "up";
+const DIRECTION_DOWN: &str = "down";
+
+/// How long the lock of a run that stopped renewing it, e.g. a killed deploy job, is kept by default.
+pub const DEFAULT_LOCK_TTL: Duration = Duration::from_secs(600);
+
+/// Columns of the tracker table added after its first version, with their type.
+const TRACKER_COLUMNS: &[(&str, &str)] = &[
+    ("checksum", "TEXT"),
+    ("duration_ms", "BIGINT"),
+    ("applied_by", "TEXT"),
+    ("hostname", "TEXT"),
+    ("tool_version", "TEXT"),
+    ("description", "TEXT"),
+    ("run_id", "UUID"),
+    ("rolled_back_at", "TIMESTAMP"),
+    ("error", "TEXT"),
+    ("error_statement", "INT"),
+    ("error_cql", "TEXT"),
+    ("statements_done", "INT"),
+    ("statements_done_checksum", "TEXT"),
+    ("direction", "TEXT"),
+];
+
+/// Replication class of the keyspaces of a single region Amazon Keyspaces deployment.
+const SINGLE_REGION_STRATEGY: &str = "SingleRegionStrategy";
+
+/// How long a schema change waits for every node to agree on the new schema by default.
+pub const DEFAULT_SCHEMA_AGREEMENT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long to wait for Amazon Keyspaces to make a created or altered keyspace or table active.
+const ACTIVE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How often Amazon Keyspaces is asked whether a keyspace or table is active yet.
+const ACTIVE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Keyspace and table names usable without quoting.
+fn is_identifier(name: &str) -> bool {
+    (1..=48).contains(&name.len())
+        && name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+pub struct ConnectOptions {
+    /// Contact point, or comma separated contact points, e.g. `10.0.0.1:9042,10.0.0.2:9042`.
+    pub db_url: String,
+    /// The config profile the settings came from, named in the errors of the run. None when no profile is selected.
+    pub environment: Option<String>,
+    pub authenticator: Option<Arc<dyn AuthenticatorProvider>>,
+    pub ssl_context: Option<SslContext>,
+    pub insecure_skip_verify: bool,
+    pub proxy: ProxyOptions,
+    /// Connects to a DataStax Astra database through the SNI proxy of its secure connect bundle instead of the db url.
+    pub astra_bundle: Option<astra::Bundle>,
+    /// Compression of the CQL frames, for large seeds and backfills over WAN links. None when not set.
+    pub compression: Option<Compression>,
+    /// Connections kept to every shard or host. One per shard when not set.
+    pub pool_size: Option<PoolSize>,
+    /// Interval of the TCP keepalive probes of idle connections, so NAT gateways and firewalls don't drop them
+    /// during long statements. No TCP keepalive when not set.
+    pub tcp_keepalive: Option<Duration>,
+    /// Disables Nagle's algorithm. Driver default (on) when not set.
+    pub tcp_nodelay: Option<bool>,
+    /// How long a schema change waits for every node to agree on the new schema.
+    pub schema_agreement_timeout: Duration,
+    /// How long the migration lock outlives its last renewal, which happens every third of it while the run goes on.
+    pub lock_ttl: Duration,
+    /// Values of the `${NAME}` placeholders of the migration files, replaced when they execute.
+    pub vars: BTreeMap<String, String>,
+    /// Bounds establishing the session, including the initial handshakes.
+    pub connect_timeout: Option<Duration>,
+    /// Bounds every single statement. Driver default when not set.
+    pub request_timeout: Option<Duration>,
+    /// Bounds all the statements of a migration file together. A migration running past it is recorded as failed.
+    pub migration_timeout: Option<Duration>,
+    pub reconnect: ReconnectPolicy,
+    pub retry: RetryPolicy,
+    /// How often cluster metadata is refreshed while a run is in progress.
+    pub refresh_interval: Duration,
+    /// Replication of the tracker keyspace, altered to match when it differs. A single replica, and the existing keyspace
+    /// left alone, when not set.
+    pub tracker_replication: Option<Replication>,
+    /// Consistency of every statement: the migrations and the tracker reads and writes, unless set more specifically.
+    /// Driver default when not set.
+    pub consistency: Option<Consistency>,
+    /// Datacenter whose nodes coordinate every statement, so DDL run from one region isn't routed through remote
+    /// coordinators. Any datacenter when not set.
+    pub local_dc: Option<String>,
+    /// Serial consistency of conditional (LWT) statements, deciding where their Paxos rounds run. Driver default when not set.
+    pub serial_consistency: Option<SerialConsistency>,
+    /// Consistency of the history reads deciding what is applied. `consistency` when not set.
+    pub history_consistency: Option<Consistency>,
+    /// Skips creating the tracker keyspace and tables, for runs that must not write anything, like dry runs.
+    /// A missing tracker reads as an empty history.
+    pub read_only: bool,
+    /// Connected to Amazon Keyspaces, which creates and alters keyspaces and tables asynchronously. Statements
+    /// creating or altering them wait until they are active, so the next one can use them.
+    pub amazon_keyspaces: bool,
+}
+
+/// Parses a consistency level name, e.g. `QUORUM` or `local_quorum`. `None` when empty.
+pub fn parse_consistency(value: &str) -> anyhow::Result<Option<Consistency>> {
+    let consistency = match value.to_uppercase().as_str() {
+        "" => return Ok(None),
+        "ANY" => Consistency::Any,
+        "ONE" => Consistency::One,
+        "TWO" => Consistency::Two,
+        "THREE" => Consistency::Three,
+        "QUORUM" => Consistency::Quorum,
+        "ALL" => Consistency::All,
+        "LOCAL_QUORUM" => Consistency::LocalQuorum,
+        "EACH_QUORUM" => Consistency::EachQuorum,
+        "LOCAL_ONE" => Consistency::LocalOne,
+        _ => return Err(anyhow::anyhow!("Unknown consistency: [{value}]")),
+    };
+
+    Ok(Some(consistency))
+}
+
+/// Parses durations like `500ms`, `30s`, `5m` or `1h`. A bare number is in seconds.
+/// Returns `None` for an empty value.
+pub fn parse_duration(value: &str) -> anyhow::Result<Option<Duration>> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid duration: [{value}]"))?;
+
+    let seconds = |per_unit: u64| {
+        amount
+            .checked_mul(per_unit)
+            .map(Duration::from_secs)
+            .ok_or_else(|| anyhow::anyhow!("Invalid duration: [{value}]"))
+    };
+    let duration = match unit {
+        "ms" => Duration::from_millis(amount),
+        "" | "s" => Duration::from_secs(amount),
+        "m" => seconds(60)?,
+        "h" => seconds(60 * 60)?,
+        _ => return Err(anyhow::anyhow!("Invalid duration unit in [{value}]. Use ms, s, m or h")),
+    };
+
+    Ok(Some(duration))
+}
+
+/// Parses a compression name, `lz4` or `snappy`. `None` when empty or `none`.
+pub fn parse_compression(value: &str) -> anyhow::Result<Option<Compression>> {
+    match value.to_lowercase().as_str() {
+        "" | "none" => Ok(None),
+        "lz4" => Ok(Some(Compression::Lz4)),
+        "snappy" => Ok(Some(Compression::Snappy)),
+        _ => Err(anyhow::anyhow!("Unknown compression: [{value}]. Use lz4, snappy or none")),
+    }
+}
+
+/// Parses a connection pool size: `<n>` or `<n>/shard` connections per shard, or `<n>/host` per host, for clusters
+/// reached without the shard aware port. `None` when empty.
+pub fn parse_pool_size(value: &str) -> anyhow::Result<Option<PoolSize>> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+
+    let (size, per) = value.split_once('/').unwrap_or((value, "shard"));
+    let size = size.trim().parse().map_err(|_| anyhow::anyhow!("Invalid pool size: [{value}]. Use e.g. 2, 2/shard or 4/host"))?;
+    match per.trim().to_lowercase().as_str() {
+        "shard" => Ok(Some(PoolSize::PerShard(size))),
+        "host" => Ok(Some(PoolSize::PerHost(size))),
+        _ => Err(anyhow::anyhow!("Invalid pool size: [{value}]. Use e.g. 2, 2/shard or 4/host")),
+    }
+}
+
+/// Parses a serial consistency level name, `SERIAL` or `LOCAL_SERIAL`. `None` when empty.
+pub fn parse_serial_consistency(value: &str) -> anyhow::Result<Option<SerialConsistency>> {
+    match value.to_uppercase().as_str() {
+        "" => Ok(None),
+        "SERIAL" => Ok(Some(SerialConsistency::Serial)),
+        "LOCAL_SERIAL" => Ok(Some(SerialConsistency::LocalSerial)),
+        _ => Err(anyhow::anyhow!("Unknown serial consistency: [{value}]. Use SERIAL or LOCAL_SERIAL")),
+    }
+}
+
+/// Replication of the tracker keyspace.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Replication {
+    /// `SimpleStrategy`, for single datacenter clusters.
+    Simple(usize),
+    /// `NetworkTopologyStrategy` with the same factor in every datacenter.
+    NetworkTopology(usize),
+    /// `NetworkTopologyStrategy` with a factor per datacenter.
+    Datacenters(Vec<(String, usize)>),
+    /// `SingleRegionStrategy` of Amazon Keyspaces, which replicates across the availability zones of its region.
+    SingleRegion,
+}
+
+impl Replication {
+    /// The `WITH REPLICATION` map.
+    fn cql(&self) -> String {
+        match self {
+            Replication::Simple(factor) => format!("{{'class' : 'SimpleStrategy', 'replication_factor' : {factor}}}"),
+            Replication::NetworkTopology(factor) => {
+                format!("{{'class' : 'NetworkTopologyStrategy', 'replication_factor' : {factor}}}")
+            }
+            Replication::Datacenters(datacenters) => {
+                let factors = datacenters.iter().map(|(dc, factor)| format!("'{dc}' : {factor}")).collect::<Vec<_>>().join(", ");
+                format!("{{'class' : 'NetworkTopologyStrategy', {factors}}}")
+            }
+            Replication::SingleRegion => format!("{{'class' : '{SINGLE_REGION_STRATEGY}'}}"),
+        }
+    }
+
+    /// Whether `strategy` already replicates like this.
+    fn matches(&self, strategy: &Strategy) -> bool {
+        match (self, strategy) {
+            (Replication::Simple(factor), Strategy::SimpleStrategy { replication_factor }) => factor == replication_factor,
+            (Replication::NetworkTopology(factor), Strategy::NetworkTopologyStrategy { datacenter_repfactors }) => {
+                datacenter_repfactors.values().all(|f| f == factor)
+            }
+            (Replication::Datacenters(datacenters), Strategy::NetworkTopologyStrategy { datacenter_repfactors }) => {
+                datacenters.iter().cloned().collect::<HashMap<_, _>>() == *datacenter_repfactors
+            }
+            (Replication::SingleRegion, Strategy::Other { name, .. }) => name.ends_with(SINGLE_REGION_STRATEGY),
+            _ => false,
+        }
+    }
+}
+
+/// Parses the replication of the tracker keyspace: per datacenter factors, e.g. `dc1:3,dc2:3`, or a single factor,
+/// e.g. `3`, applied to every datacenter. `class` is `NetworkTopologyStrategy`, the default, `SimpleStrategy`,
+/// which takes a single factor, or `SingleRegionStrategy`, which takes none. `None` when `value` is empty.
+pub fn parse_replication(class: &str, value: &str) -> anyhow::Result<Option<Replication>> {
+    let value = value.trim();
+    let simple = match class {
+        "" | "NetworkTopologyStrategy" => false,
+        "SimpleStrategy" => true,
+        SINGLE_REGION_STRATEGY if value.is_empty() => return Ok(Some(Replication::SingleRegion)),
+        SINGLE_REGION_STRATEGY => return Err(anyhow::anyhow!("{SINGLE_REGION_STRATEGY} takes no replication factor, got [{value}]")),
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Unknown replication class: [{class}]. Use NetworkTopologyStrategy, SimpleStrategy or {SINGLE_REGION_STRATEGY}"
+            ))
+        }
+    };
+    if value.is_empty() {
+        if simple {
+            return Err(anyhow::anyhow!("SimpleStrategy needs a replication factor"));
+        }
+        return Ok(None);
+    }
+
+    if let Ok(factor) = value.parse() {
+        return Ok(Some(if simple { Replication::Simple(factor) } else { Replication::NetworkTopology(factor) }));
+    }
+    if simple {
+        return Err(anyhow::anyhow!("SimpleStrategy takes a single replication factor, got [{value}]"));
+    }
+
+    let datacenters = value
+        .split(',')
+        .map(str::trim)
+        .filter(|dc| !dc.is_empty())
+        .map(|dc| {
+            let (name, factor) = dc.split_once(':').ok_or_else(|| anyhow::anyhow!("Expected <datacenter>:<factor>, got [{dc}]"))?;
+            let factor = factor.trim().parse().map_err(|_| anyhow::anyhow!("Invalid replication factor: [{dc}]"))?;
+            Ok((name.trim().to_string(), factor))
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    Ok(Some(Replication::Datacenters(datacenters)))
+}
+
+/// Retries of a migration statement failing with a timeout, overload or unavailable error, waiting `backoff`
+/// before the first one and twice as long before each next one. Meant for idempotent statements: a timed out
+/// statement may still have been applied.
+#[derive(Debug, Clone, Default)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub backoff: Duration,
+}
+
+/// How long to keep trying to get a working session back when the cluster becomes
+/// unreachable between migrations. A zero timeout disables reconnecting.
+pub struct ReconnectPolicy {
+    pub timeout: Duration,
+    pub interval: Duration,
+}
+
+/// A tracker row.
+#[derive(Debug, Clone, FromRow)]
+pub(crate) struct MigrationData {
+    pub(crate) id: String,
+    /// `success`, `failed`, or `rolled_back` once reverted by `down`.
+    pub(crate) status: String,
+    pub(crate) run_at: Option<chrono::DateTime<Utc>>,
+    /// SHA-256 of the up.cql that ran. Missing for rows written by older versions.
+    pub(crate) checksum: Option<String>,
+    /// How long the run took. Missing for rows recorded without running, and rows written by older versions.
+    pub(crate) duration_ms: Option<i64>,
+    /// OS user and machine that ran it, and the version of the tool. Missing for rows written by older versions.
+    pub(crate) applied_by: Option<String>,
+    pub(crate) hostname: Option<String>,
+    pub(crate) tool_version: Option<String>,
+    /// What the migration does, from its name or its `description` frontmatter.
+    pub(crate) description: Option<String>,
+    /// The `up` invocation that applied it. Missing for rows recorded without running, and rows written by older versions.
+    pub(crate) run_id: Option<Uuid>,
+    /// When `down` reverted it, its other columns still describing the run that applied it.
+    pub(crate) rolled_back_at: Option<chrono::DateTime<Utc>>,
+    /// Why the run failed, and the 1-based index and text of the failing statement, for failed rows.
+    pub(crate) error: Option<String>,
+    pub(crate) error_statement: Option<i32>,
+    pub(crate) error_cql: Option<String>,
+    /// How many statements of the file the failed run completed, and their checksum, for `up --resume`.
+    /// For successful runs, the statements a resumed run skipped.
+    pub(crate) statements_done: Option<i32>,
+    pub(crate) statements_done_checksum: Option<String>,
+    /// `up`, or `down` for a row recording a failed down. Missing for rows written by older versions, which are `up`.
+    pub(crate) direction: Option<String>,
+}
+
+impl MigrationData {
+    /// Whether the row records a run of the up.cql, the only runs `up --resume` continues.
+    pub(crate) fn is_up(&self) -> bool {
+        self.direction.as_deref() != Some(DIRECTION_DOWN)
+    }
+}
+
+/// The contact points of a db url. Any one of them being reachable is enough to connect.
+fn contact_points(db_url: &str) -> Vec<&str> {
+    db_url.split(',').map(str::trim).filter(|node| !node.is_empty()).collect()
+}
+
+pub(crate) async fn session(options: &ConnectOptions) -> anyhow::Result<Session> {
+    let mut builder = SessionBuilder::new();
+    let nodes = contact_points(&options.db_url);
+    let mut local_dc = options.local_dc.clone();
+    if let Some(bundle) = &options.astra_bundle {
+        let astra = Astra::start(bundle).await?;
+        for host_id in &astra.contact_points {
+            builder = builder.known_node_addr(astra.local_address(host_id).await?);
+        }
+        local_dc = local_dc.or_else(|| astra.local_dc.clone());
+        builder = builder
+            .address_translator(Arc::new(astra))
+            .disallow_shard_aware_port(true);
+    } else if let Some(proxy) = Proxy::start(&options.proxy, nodes.first().copied().unwrap_or_default()).await? {
+        for node in &nodes {
+            builder = builder.known_node_addr(proxy.local_address(node).await?);
+        }
+        builder = builder
+            .address_translator(Arc::new(proxy))
+            .disallow_shard_aware_port(true);
+    } else {
+        builder = builder.known_nodes(&nodes);
+    }
+
+    if let Some(authenticator) = &options.authenticator {
+        builder = builder.authenticator_provider(authenticator.clone());
+    }
+
+    if let Some(connect_timeout) = options.connect_timeout {
+        builder = builder.connection_timeout(connect_timeout);
+    }
+
+    // unset options keep the driver defaults of an empty profile
+    let mut profile = ExecutionProfile::builder();
+    if let Some(request_timeout) = options.request_timeout {
+        profile = profile.request_timeout(Some(request_timeout));
+    }
+    if let Some(consistency) = options.consistency {
+        profile = profile.consistency(consistency);
+    }
+    if let Some(serial_consistency) = options.serial_consistency {
+        profile = profile.serial_consistency(Some(serial_consistency));
+    }
+    if let Some(local_dc) = local_dc {
+        let policy = DefaultPolicy::builder().prefer_datacenter(local_dc).permit_dc_failover(false).build();
+        profile = profile.load_balancing_policy(policy);
+    }
+    builder = builder.default_execution_profile_handle(profile.build().into_handle());
+
+    if let Some(pool_size) = options.pool_size {
+        builder = builder.pool_size(pool_size);
+    }
+    if let Some(tcp_keepalive) = options.tcp_keepalive {
+        builder = builder.tcp_keepalive_interval(tcp_keepalive);
+    }
+    if let Some(tcp_nodelay) = options.tcp_nodelay {
+        builder = builder.tcp_nodelay(tcp_nodelay);
+    }
+    builder = builder.schema_agreement_timeout(options.schema_agreement_timeout);
+
+    let builder = builder.compression(options.compression).ssl_context(options.ssl_context.clone());
+    let session = match options.connect_timeout {
+        Some(connect_timeout) => tokio::time::timeout(connect_timeout, builder.build())
+            .await
+            .map_err(|_| anyhow::anyhow!("Timed out connecting to [{}] after {connect_timeout:?}", options.db_url))??,
+        None => builder.build().await?,
+    };
+    if options.read_only {
+        return Ok(session);
+    }
+
+    let replication = match &options.tracker_replication {
+        Some(replication) => replication.cql(),
+        None if options.amazon_keyspaces => Replication::SingleRegion.cql(),
+        None => "{'class' : 'NetworkTopologyStrategy', 'replication_factor' : 1}".to_string(),
+    };
+    let keyspace = keyspace();
+    let table = &tracker().table;
+    if let Some(bundle) = &options.astra_bundle {
+        // Astra only creates keyspaces from its console
+        session.refresh_metadata().await?;
+        if !session.get_cluster_data().get_keyspace_info().contains_key(keyspace) {
+            let existing = bundle.keyspace.as_deref().map(|k| format!(", e.g. {k}")).unwrap_or_default();
+            return Err(anyhow::anyhow!(
+                "No {keyspace} keyspace: create it in the Astra console, or pass an existing one as the history keyspace{existing}"
+            ));
+        }
+    } else {
+        session
+            .query_unpaged(
+                format!(
+                    "
+                CREATE KEYSPACE IF NOT EXISTS {keyspace}
+                WITH REPLICATION = {replication}
+                "
+                ),
+                &[],
+            )
+            .await?;
+    }
+    if options.amazon_keyspaces {
+        wait_until_active(&session, keyspace, None).await?;
+    }
+    if let Some(wanted) = &options.tracker_replication {
+        ensure_replication(&session, wanted).await?;
+    }
 
     session
         .query_unpaged(
+            format!(
+                "
+            CREATE TABLE IF NOT EXISTS {keyspace}.{table}
+            (
+                type TEXT,
+                id TEXT,
+                status TEXT,
+                run_at TIMESTAMP,
+                checksum TEXT,
+                duration_ms BIGINT,
+                applied_by TEXT,
+                hostname TEXT,
+                tool_version TEXT,
+                description TEXT,
+                run_id UUID,
+                rolled_back_at TIMESTAMP,
+                error TEXT,
+                error_statement INT,
+                error_cql TEXT,
+                statements_done INT,
+                statements_done_checksum TEXT,
+                direction TEXT,
+
+                PRIMARY KEY (type, id)
+            )
             "
-            CREATE KEYSPACE IF NOT EXISTS scylladb_migrate_ks
-            WITH REPLICATION = {'class' : 'NetworkTopologyStrategy', 'replication_factor' : 1}
-            ",
+            ),
             &[],
         )
         .await?;
+    add_missing_columns(&session, table, TRACKER_COLUMNS, options.amazon_keyspaces).await?;
+    if options.amazon_keyspaces {
+        wait_until_active(&session, keyspace, Some(table)).await?;
+    }
 
     session
         .query_unpaged(
+            format!(
+                "
+            CREATE TABLE IF NOT EXISTS {keyspace}.{SCAN_PROGRESS}
+            (
+                scan TEXT,
+                ranges INT,
+                range INT,
+                rows BIGINT,
+                done_at TIMESTAMP,
+
+                PRIMARY KEY ((scan, ranges), range)
+            )
             "
-            CREATE TABLE IF NOT EXISTS scylladb_migrate_ks.migrations
+            ),
+            &[],
+        )
+        .await?;
+    if options.amazon_keyspaces {
+        wait_until_active(&session, keyspace, Some(SCAN_PROGRESS)).await?;
+    }
+
+    session
+        .query_unpaged(
+            format!(
+                "
+            CREATE TABLE IF NOT EXISTS {keyspace}.{MIGRATION_LOCK}
             (
-                type TEXT,
-                id TEXT,
-                status TEXT,
-                run_at TIMESTAMP,
+                name TEXT,
+                owner TEXT,
+                acquired_at TIMESTAMP,
 
-                PRIMARY KEY (type, id)
+                PRIMARY KEY (name)
             )
-            ",
+            "
+            ),
             &[],
         )
         .await?;
+    if options.amazon_keyspaces {
+        wait_until_active(&session, keyspace, Some(MIGRATION_LOCK)).await?;
+    }
 
     Ok(session)
 }
 
-pub(crate) async fn upsert(
-    session: &Session,
-    migration: String,
-    success: bool,
-    now: chrono::DateTime<Utc>,
-) -> anyhow::Result<()> {
-    let status = if success { "success" } else { "failed" };
+/// Whether `error` is the driver giving up waiting for schema agreement after a schema change, which was applied
+/// on the coordinator and must not be retried.
+pub(crate) fn schema_disagreement(error: &QueryError) -> bool {
+    matches!(error, QueryError::RequestTimeout(message) if message.contains("schema agreement"))
+}
+
+/// Waits until Amazon Keyspaces has made `table` of `keyspace`, or the keyspace itself without a table, active.
+/// Its DDL statements return before they take effect, and a table being created or altered can't be used yet.
+pub(crate) async fn wait_until_active(session: &Session, keyspace: &str, table: Option<&str>) -> anyhow::Result<()> {
+    let deadline = tokio::time::Instant::now() + ACTIVE_TIMEOUT;
+    loop {
+        let active = match table {
+            Some(table) => session
+                .query_unpaged(
+                    "SELECT status FROM system_schema_mcs.tables WHERE keyspace_name = ? AND table_name = ?",
+                    (keyspace, table),
+                )
+                .await?
+                .maybe_first_row_typed::<(Option<String>,)>()?
+                .is_some_and(|(status,)| status.as_deref() == Some("ACTIVE")),
+            None => session
+                .query_unpaged("SELECT keyspace_name FROM system_schema_mcs.keyspaces WHERE keyspace_name = ?", (keyspace,))
+                .await?
+                .maybe_first_row_typed::<(String,)>()?
+                .is_some(),
+        };
+        if active {
+            return Ok(());
+        }
+
+        let object = table.map_or_else(|| keyspace.to_string(), |table| format!("{keyspace}.{table}"));
+        if tokio::time::Instant::now() >= deadline {
+            return Err(anyhow::anyhow!("{object} is still not active after {ACTIVE_TIMEOUT:?}"));
+        }
+        log::info!("waiting for {object} to become active");
+        tokio::time::sleep(ACTIVE_POLL_INTERVAL).await;
+    }
+}
+
+/// Brings a tracker table created by an older version forward. Its schema version is the number of
+/// `columns`, added in order and never dropped, it already has, plus one for the first version.
+/// Another runner adding the same column at the same time is not an error.
+async fn add_missing_columns(session: &Session, table: &str, columns: &[(&str, &str)], amazon_keyspaces: bool) -> anyhow::Result<()> {
+    let missing = missing_columns(session, table, columns).await?;
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let keyspace = keyspace();
+    let version = columns.len() - missing.len() + 1;
+    log::info!("upgrading {keyspace}.{table} from schema version {version} to {}", columns.len() + 1);
+    for (column, cql_type) in columns.iter().filter(|(column, _)| missing.contains(column)) {
+        log::info!("adding {column} to {keyspace}.{table}");
+        let added = session.query_unpaged(format!("ALTER TABLE {keyspace}.{table} ADD {column} {cql_type}"), &[]).await;
+        if let Err(e) = added {
+            if missing_columns(session, table, columns).await?.contains(column) {
+                return Err(anyhow::anyhow!("Unable to add {column} to {keyspace}.{table}: {e}"));
+            }
+        }
+        if amazon_keyspaces {
+            // Amazon Keyspaces refuses to alter a table until the previous change is done
+            wait_until_active(session, keyspace, Some(table)).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The `columns` `table` lacks, none when the table doesn't exist.
+async fn missing_columns<'a>(session: &Session, table: &str, columns: &[(&'a str, &str)]) -> anyhow::Result<Vec<&'a str>> {
+    session.refresh_metadata().await?;
+    let cluster = session.get_cluster_data();
+    let Some(existing) = cluster.get_keyspace_info().get(keyspace()).and_then(|keyspace| keyspace.tables.get(table)) else {
+        return Ok(vec![]);
+    };
+
+    Ok(columns.iter().map(|(column, _)| *column).filter(|column| !existing.columns.contains_key(*column)).collect())
+}
+
+/// Columns an existing tracker lacks, for read only sessions, which don't upgrade it.
+pub(crate) async fn missing_tracker_columns(session: &Session) -> anyhow::Result<Vec<&'static str>> {
+    missing_columns(session, &tracker().table, TRACKER_COLUMNS).await
+}
+
+/// Whether the tracker table exists, for read only sessions, which don't create it.
+pub(crate) async fn tracker_exists(session: &Session) -> anyhow::Result<bool> {
+    session.refresh_metadata().await?;
+    Ok(session
+        .get_cluster_data()
+        .get_keyspace_info()
+        .get(keyspace())
+        .is_some_and(|keyspace| keyspace.tables.contains_key(&tracker().table)))
+}
+
+/// Alters the tracker keyspace when it was created with another replication than `wanted`.
+async fn ensure_replication(session: &Session, wanted: &Replication) -> anyhow::Result<()> {
+    session.refresh_metadata().await?;
+    let cluster = session.get_cluster_data();
+    let Some(current) = cluster.get_keyspace_info().get(keyspace()).map(|k| &k.strategy) else {
+        return Ok(());
+    };
+    if wanted.matches(current) {
+        return Ok(());
+    }
+
+    let keyspace = keyspace();
+    let replication = wanted.cql();
+    log::info!("altering the replication of {keyspace} from {current:?} to {replication}, repair it to stream the existing history");
+    session
+        .query_unpaged(format!("ALTER KEYSPACE {keyspace} WITH REPLICATION = {replication}"), &[])
+        .await?;
+    session.refresh_metadata().await?;
+
+    Ok(())
+}
+
+/// Tracker writes reach a quorum in every datacenter when the tracker keyspace spans several,
+/// so the history survives losing one of them.
+fn tracker_write(session: &Session, text: &str) -> Query {
+    let mut query = Query::new(text);
+    let cluster = session.get_cluster_data();
+    if let Some(Strategy::NetworkTopologyStrategy { datacenter_repfactors }) = cluster.get_keyspace_info().get(keyspace()).map(|k| &k.strategy) {
+        if datacenter_repfactors.len() > 1 {
+            query.set_consistency(Consistency::EachQuorum);
+        }
+    }
+    query
+}
+
+/// Makes sure `session` can still reach the cluster, replacing it with a fresh one if it can't.
+/// Returns whether a reconnect happened, in which case the caller should re-read the tracker.
+pub(crate) async fn ensure_connected(session: &mut Session, options: &ConnectOptions) -> anyhow::Result<bool> {
+    let Err(err) = probe(session).await else {
+        return Ok(false);
+    };
+
+    let policy = &options.reconnect;
+    if policy.timeout.is_zero() {
+        return Err(anyhow::anyhow!("Lost connection to the cluster: {err}"));
+    }
+
+    log::warn!("lost connection to the cluster: {err}. Reconnecting for up to {:?}", policy.timeout);
+
+    let started = tokio::time::Instant::now();
+    loop {
+        tokio::time::sleep(policy.interval).await;
+
+        match self::session(options).await {
+            Ok(new_session) => match probe(&new_session).await {
+                Ok(()) => {
+                    log::info!("reconnected to the cluster");
+                    *session = new_session;
+                    return Ok(true);
+                }
+                Err(e) => log::warn!("reconnect attempt failed: {e}"),
+            },
+            Err(e) => log::warn!("reconnect attempt failed: {e}"),
+        }
+
+        if started.elapsed() >= policy.timeout {
+            return Err(anyhow::anyhow!("Unable to reconnect to the cluster within {:?}", policy.timeout));
+        }
+    }
+}
+
+async fn probe(session: &Session) -> anyhow::Result<()> {
+    session
+        .query_unpaged("SELECT key FROM system.local", &[])
+        .await?;
+
+    Ok(())
+}
+
+/// Refreshes cluster metadata between statements once `interval` has passed, so multi-hour
+/// runs don't keep working with a topology and schema view from when they started.
+pub(crate) struct MetadataRefresh {
+    interval: Duration,
+    last: tokio::time::Instant,
+}
+
+impl MetadataRefresh {
+    pub(crate) fn new(interval: Duration) -> Self {
+        MetadataRefresh { interval, last: tokio::time::Instant::now() }
+    }
+
+    pub(crate) async fn tick(&mut self, session: &Session) -> anyhow::Result<()> {
+        if self.interval.is_zero() || self.last.elapsed() < self.interval {
+            return Ok(());
+        }
+
+        session
+            .refresh_metadata()
+            .await
+            .map_err(|e| anyhow::anyhow!("Unable to refresh cluster metadata, aborting: {e}"))?;
+        self.last = tokio::time::Instant::now();
+
+        Ok(())
+    }
+}
+
+/// A run of a migration, as recorded in the tracker.
+#[derive(Debug, Default)]
+pub(crate) struct Run {
+    pub(crate) success: bool,
+    pub(crate) run_at: chrono::DateTime<Utc>,
+    /// SHA-256 of the up.cql that ran.
+    pub(crate) checksum: Option<String>,
+    /// How long its statements took. `None` for migrations recorded without running them.
+    pub(crate) duration: Option<Duration>,
+    pub(crate) description: Option<String>,
+    /// The `up` invocation that applied it, shared by every migration it applied.
+    pub(crate) run_id: Option<Uuid>,
+    /// Why it failed, for failed runs that executed statements.
+    pub(crate) failure: Option<Failure>,
+    /// Whether it ran the down.cql, for failed downs.
+    pub(crate) down: bool,
+    /// The statements a resumed run skipped, completed by the failed run before it.
+    pub(crate) skipped: usize,
+}
+
+/// Why a run failed, as recorded in the tracker.
+#[derive(Debug, Default)]
+pub(crate) struct Failure {
+    /// The driver error, or the timeout or cancellation that stopped the run.
+    pub(crate) message: Option<String>,
+    /// 1-based index of the failing statement within its file, and its text.
+    pub(crate) statement: Option<usize>,
+    pub(crate) cql: Option<String>,
+    /// How many statements completed before the failing one, and their checksum.
+    pub(crate) statements_done: Option<usize>,
+    pub(crate) statements_done_checksum: Option<String>,
+}
 
+/// A fresh random id for an `up` invocation.
+pub(crate) fn new_run_id() -> anyhow::Result<Uuid> {
+    let mut bytes = [0; 16];
+    openssl::rand::rand_bytes(&mut bytes)?;
+    Ok(uuid::Builder::from_random_bytes(bytes).into_uuid())
+}
+
+/// The migration lock held by this run. Only one run at a time gets it, with an `IF NOT EXISTS` insert, for each
+/// history: runs tracked in another table or partition don't wait for each other.
+pub(crate) struct Lock {
+    /// Who holds it, and a random id telling apart runs of the same user on the same machine.
+    owner: String,
+    acquired_at: chrono::DateTime<Utc>,
+    ttl: Duration,
+    renewed: std::sync::Mutex<tokio::time::Instant>,
+}
+
+/// Row of the migration lock of the history.
+fn lock_name() -> String {
+    format!("{}.{}", tracker().table, partition())
+}
+
+/// Takes the migration lock, for `ttl` unless renewed. Fails, naming the holder, when another run has it.
+pub(crate) async fn lock(session: &Session, ttl: Duration) -> anyhow::Result<Lock> {
+    let origin = origin();
+    let who = match (&origin.user, &origin.hostname) {
+        (Some(user), Some(hostname)) => format!("{user}@{hostname}"),
+        (Some(who), None) | (None, Some(who)) => who.clone(),
+        (None, None) => "unknown".to_string(),
+    };
+    let owner = format!("{who} ({})", new_run_id()?);
+    let acquired_at = Utc::now();
+
+    let result = session
+        .query_unpaged(
+            format!(
+                "INSERT INTO {}.{MIGRATION_LOCK} (name, owner, acquired_at) VALUES (?, ?, ?) IF NOT EXISTS USING TTL ?",
+                keyspace()
+            ),
+            (lock_name(), &owner, acquired_at, ttl_seconds(ttl)),
+        )
+        .await?;
+    if !lwt_applied(&result) {
+        let holder = lwt_column(&result, "owner").and_then(|owner| owner.as_text().cloned()).unwrap_or_default();
+        let since = lwt_column(&result, "acquired_at")
+            .and_then(CqlValue::as_cql_timestamp)
+            .and_then(|at| chrono::DateTime::from_timestamp_millis(at.0))
+            .map(|at| at.format(" since %Y-%m-%d %H:%M:%S UTC").to_string())
+            .unwrap_or_default();
+        return Err(anyhow::anyhow!(
+            "The migration lock is held by {holder}{since}. Wait for that run to finish, or run `unlock` if it was killed: \
+            the lock expires on its own {ttl:?} after its last renewal"
+        ));
+    }
+
+    Ok(Lock { owner, acquired_at, ttl, renewed: std::sync::Mutex::new(tokio::time::Instant::now()) })
+}
+
+impl Lock {
+    /// Extends the lock for another `ttl` once a third of it has passed. Fails when it expired and another run took it.
+    pub(crate) async fn renew(&self, session: &Session) -> anyhow::Result<()> {
+        if self.renewed.lock().unwrap().elapsed() < self.ttl / 3 {
+            return Ok(());
+        }
+
+        let result = session
+            .query_unpaged(
+                format!(
+                    "UPDATE {}.{MIGRATION_LOCK} USING TTL ? SET owner = ?, acquired_at = ? WHERE name = ? IF owner = ?",
+                    keyspace()
+                ),
+                (ttl_seconds(self.ttl), &self.owner, self.acquired_at, lock_name(), &self.owner),
+            )
+            .await?;
+        if !lwt_applied(&result) {
+            return Err(anyhow::anyhow!(
+                "Lost the migration lock: it expired, and another run may be changing the schema. Raise the lock TTL"
+            ));
+        }
+
+        *self.renewed.lock().unwrap() = tokio::time::Instant::now();
+        Ok(())
+    }
+
+    /// Renews the lock every third of its TTL for as long as it is awaited, so a statement running longer than the TTL
+    /// doesn't lose it. Only returns once a renewal fails, with why.
+    pub(crate) async fn heartbeat(&self, session: &Session) -> anyhow::Error {
+        loop {
+            tokio::time::sleep(self.ttl / 3).await;
+            if let Err(e) = self.renew(session).await {
+                return e;
+            }
+        }
+    }
+
+    /// Gives the lock up, unless another run took it over after it expired.
+    pub(crate) async fn release(self, session: &Session) -> anyhow::Result<()> {
+        session
+            .query_unpaged(
+                format!("DELETE FROM {}.{MIGRATION_LOCK} WHERE name = ? IF owner = ?", keyspace()),
+                (lock_name(), &self.owner),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Removes the migration lock whoever holds it, for a run killed before releasing it. Returns the holder.
+pub(crate) async fn unlock(session: &Session) -> anyhow::Result<Option<String>> {
+    let result = session
+        .query_unpaged(format!("DELETE FROM {}.{MIGRATION_LOCK} WHERE name = ? IF EXISTS", keyspace()), (lock_name(),))
+        .await?;
+
+    Ok(lwt_column(&result, "owner").and_then(|owner| owner.as_text().cloned()))
+}
+
+fn ttl_seconds(ttl: Duration) -> i32 {
+    ttl.as_secs().clamp(1, i32::MAX as u64) as i32
+}
+
+/// Whether a conditional statement was applied, from its `[applied]` column.
+fn lwt_applied(result: &scylla::QueryResult) -> bool {
+    lwt_column(result, "[applied]").and_then(CqlValue::as_boolean).unwrap_or(false)
+}
+
+/// The `name` column of the row a conditional statement replies with.
+fn lwt_column<'a>(result: &'a scylla::QueryResult, name: &str) -> Option<&'a CqlValue> {
+    let (index, _) = result.get_column_spec(name)?;
+    result.rows.as_ref()?.first()?.columns.get(index)?.as_ref()
+}
+
+/// Version of the tool recorded with every run.
+const TOOL_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Who ran the tool, and where.
+struct Origin {
+    user: Option<String>,
+    hostname: Option<String>,
+}
+
+/// The OS user, from `USER` or `USERNAME`, and the machine, from `HOSTNAME` or the `hostname` program.
+fn origin() -> &'static Origin {
+    static ORIGIN: OnceLock<Origin> = OnceLock::new();
+    ORIGIN.get_or_init(|| {
+        let user = std::env::var("USER").or_else(|_| std::env::var("USERNAME")).ok();
+        let hostname = std::env::var("HOSTNAME").ok().or_else(|| {
+            let output = std::process::Command::new("hostname").output().ok()?;
+            Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        });
+        Origin { user, hostname: hostname.filter(|hostname| !hostname.is_empty()) }
+    })
+}
+
+/// Records a run of `migration`.
+pub(crate) async fn upsert(session: &Session, migration: String, run: Run) -> anyhow::Result<()> {
+    upsert_in(session, partition(), migration, run).await
+}
+
+pub(crate) async fn list(session: &Session, consistency: Option<Consistency>) -> anyhow::Result<Vec<String>> {
+    list_in(session, partition(), consistency).await
+}
+
+pub(crate) async fn delete(session: &Session, migration: String) -> anyhow::Result<()> {
+    delete_in(session, partition(), migration).await
+}
+
+/// Marks `migration` as reverted at `at`, keeping the record of the run that applied it.
+pub(crate) async fn roll_back(session: &Session, migration: String, at: chrono::DateTime<Utc>) -> anyhow::Result<()> {
     session
         .query_unpaged(
-            "
-                INSERT INTO scylladb_migrate_ks.migrations (type, id, status, run_at)
-                VALUES (?, ?, ?, ?)
+            tracker_write(
+                session,
+                &format!(
+                    "
+                UPDATE {}
+                SET status = 'rolled_back', rolled_back_at = ?
+                WHERE type = ?
+                AND id = ?
+            ",
+                    migrations_table()
+                ),
+            ),
+            (at, partition(), migration)
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Like `upsert`, for a history kept apart from the migrations, e.g. seeds.
+pub(crate) async fn upsert_in(session: &Session, partition: &str, migration: String, run: Run) -> anyhow::Result<()> {
+    let status = if run.success { "success" } else { "failed" };
+    let duration_ms = run.duration.map(|duration| duration.as_millis() as i64);
+    let origin = origin();
+    let failure = run.failure.unwrap_or_default();
+    let direction = if run.down { DIRECTION_DOWN } else { DIRECTION_UP };
+
+    session
+        .query_unpaged(
+            tracker_write(
+                session,
+                &format!(
+                    "
+                INSERT INTO {} (type, id, status, run_at, checksum, duration_ms, applied_by, hostname, tool_version, description, run_id, rolled_back_at,
+                    error, error_statement, error_cql, statements_done, statements_done_checksum, direction)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, null, ?, ?, ?, ?, ?, '{direction}')
                 ",
-            (PARTITION_KEY, migration, status, now),
+                    migrations_table()
+                ),
+            ),
+            (
+                partition,
+                migration,
+                status,
+                run.run_at,
+                run.checksum,
+                duration_ms,
+                &origin.user,
+                &origin.hostname,
+                TOOL_VERSION,
+                run.description,
+                run.run_id,
+                failure.message,
+                failure.statement.map(|index| index as i32),
+                failure.cql,
+                failure.statements_done.or(Some(run.skipped).filter(|&skipped| skipped > 0)).map(|done| done as i32),
+                failure.statements_done_checksum,
+            ),
         )
         .await?;
 
     Ok(())
 }
 
-pub(crate) async fn list(session: &Session) -> anyhow::Result<Vec<String>> {
+pub(crate) async fn list_in(session: &Session, partition: &str, consistency: Option<Consistency>) -> anyhow::Result<Vec<String>> {
     Ok(
-        session
-            .query_unpaged(
-                "
-            SELECT id, status
-            FROM scylladb_migrate_ks.migrations
+        rows_in(session, partition, consistency)
+            .await?
+            .into_iter()
+            .filter(|r| r.status == "success")
+            .map(|r| r.id)
+            .collect()
+    )
+}
+
+/// Every tracker row of `partition`, failed ones included, by id.
+pub(crate) async fn rows_in(session: &Session, partition: &str, consistency: Option<Consistency>) -> anyhow::Result<Vec<MigrationData>> {
+    let mut query = Query::new(format!(
+        "
+            SELECT id, status, run_at, checksum, duration_ms, applied_by, hostname, tool_version, description, run_id, rolled_back_at,
+                error, error_statement, error_cql, statements_done, statements_done_checksum, direction
+            FROM {}
             WHERE type = ?
             ORDER BY id
             ",
-                (PARTITION_KEY,),
-            )
+        migrations_table()
+    ));
+    if let Some(consistency) = consistency {
+        query.set_consistency(consistency);
+    }
+
+    Ok(
+        session
+            .query_unpaged(query, (partition,))
             .await?
             .rows
             .unwrap()
             .into_typed::<MigrationData>()
-            .filter_map(|r| {
-                let r = r.ok()?;
-                if r.status == "success" {
-                    return Some(r.id);
-                }
-                None
-            })
+            .filter_map(Result::ok)
             .collect()
     )
 }
 
-pub(crate) async fn delete(session: &Session, migration: String) -> anyhow::Result<()> {
+pub(crate) async fn delete_in(session: &Session, partition: &str, migration: String) -> anyhow::Result<()> {
     session
         .query_unpaged(
-            "
-                DELETE FROM scylladb_migrate_ks.migrations
+            tracker_write(
+                session,
+                &format!(
+                    "
+                DELETE FROM {}
                 WHERE type = ?
                 AND id = ?
             ",
-            (PARTITION_KEY, migration)
+                    migrations_table()
+                ),
+            ),
+            (partition, migration)
         )
         .await?;
 
     Ok(())
 }
+
+/// Token ranges of `scan` completed so far, when the ring is split into `ranges`.
+pub(crate) async fn scan_ranges_done(session: &Session, scan: &str, ranges: usize) -> anyhow::Result<Vec<usize>> {
+    let rows = session
+        .query_unpaged(
+            format!("SELECT range FROM {}.{SCAN_PROGRESS} WHERE scan = ? AND ranges = ?", keyspace()),
+            (scan, ranges as i32),
+        )
+        .await?
+        .rows_typed::<(i32,)>()?;
+
+    let mut done = vec![];
+    for row in rows {
+        done.push(row?.0 as usize);
+    }
+
+    Ok(done)
+}
+
+pub(crate) async fn complete_scan_range(
+    session: &Session,
+    scan: &str,
+    ranges: usize,
+    range: usize,
+    rows: usize,
+) -> anyhow::Result<()> {
+    session
+        .query_unpaged(
+            tracker_write(
+                session,
+                &format!(
+                    "
+                INSERT INTO {}.{SCAN_PROGRESS} (scan, ranges, range, rows, done_at)
+                VALUES (?, ?, ?, ?, ?)
+                ",
+                    keyspace()
+                ),
+            ),
+            (scan, ranges as i32, range as i32, rows as i64, Utc::now()),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Forgets the progress of `scan`, so the next run starts over.
+pub(crate) async fn clear_scan(session: &Session, scan: &str, ranges: usize) -> anyhow::Result<()> {
+    session
+        .query_unpaged(
+            tracker_write(session, &format!("DELETE FROM {}.{SCAN_PROGRESS} WHERE scan = ? AND ranges = ?", keyspace())),
+            (scan, ranges as i32),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Removes every tracker row, including failed ones.
+pub(crate) async fn clear(session: &Session) -> anyhow::Result<()> {
+    session
+        .query_unpaged(
+            tracker_write(
+                session,
+                &format!(
+                    "
+                DELETE FROM {}
+                WHERE type = ?
+            ",
+                    migrations_table()
+                ),
+            ),
+            (partition(),)
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Tables, types, indexes and views of the application keyspaces, as the cluster sees them.
+/// System keyspaces and the tracker keyspace are left out.
+pub(crate) async fn schema_objects(session: &Session) -> anyhow::Result<Vec<(ObjectKind, Name)>> {
+    let queries = [
+        (ObjectKind::Table, "SELECT keyspace_name, table_name FROM system_schema.tables"),
+        (ObjectKind::Type, "SELECT keyspace_name, type_name FROM system_schema.types"),
+        (ObjectKind::Index, "SELECT keyspace_name, index_name FROM system_schema.indexes"),
+        (ObjectKind::MaterializedView, "SELECT keyspace_name, view_name FROM system_schema.views"),
+    ];
+
+    let mut objects = vec![];
+    for (kind, query) in queries {
+        let rows = session
+            .query_unpaged(query, &[])
+            .await?
+            .rows_typed::<(String, String)>()?;
+
+        for row in rows {
+            let (keyspace, name) = row?;
+            if keyspace.starts_with("system") || keyspace == self::keyspace() {
+                continue;
+            }
+            let name = Name { keyspace: Some(keyspace), name };
+            // managed by the cdc option of its base table
+            if kind == ObjectKind::Table && cdc::base_table(&name).is_some() {
+                continue;
+            }
+            objects.push((kind, name));
+        }
+    }
+
+    objects.sort_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(&b.0)));
+    Ok(objects)
+}
+
+/// A column of an application table, as `system_schema.columns` describes it.
+#[derive(Debug, Clone)]
+pub(crate) struct Column {
+    pub(crate) table: Name,
+    pub(crate) name: String,
+    /// `partition_key`, `clustering`, `regular` or `static`.
+    pub(crate) kind: String,
+    /// Position within the partition or clustering key.
+    pub(crate) position: i32,
+    /// CQL type, e.g. `text` or `frozen<list<int>>`.
+    pub(crate) cql_type: String,
+}
+
+/// Columns of the application tables and views, ordered by key position.
+pub(crate) async fn columns(session: &Session) -> anyhow::Result<Vec<Column>> {
+    let rows = session
+        .query_unpaged("SELECT keyspace_name, table_name, column_name, kind, position, type FROM system_schema.columns", &[])
+        .await?
+        .rows_typed::<(String, String, String, String, i32, String)>()?;
+
+    let mut columns = vec![];
+    for row in rows {
+        let (keyspace, table, name, kind, position, cql_type) = row?;
+        if keyspace.starts_with("system") || keyspace == self::keyspace() {
+            continue;
+        }
+        columns.push(Column { table: Name { keyspace: Some(keyspace), name: table }, name, kind, position, cql_type });
+    }
+
+    columns.sort_by_key(|c| c.position);
+    Ok(columns)
+}
+
+/// Names of every keyspace of the cluster.
+pub(crate) async fn keyspaces(session: &Session) -> anyhow::Result<Vec<String>> {
+    let rows = session
+        .query_unpaged("SELECT keyspace_name FROM system_schema.keyspaces", &[])
+        .await?
+        .rows_typed::<(String,)>()?;
+
+    let mut keyspaces = vec![];
+    for row in rows {
+        keyspaces.push(row?.0);
+    }
+
+    Ok(keyspaces)
+}
+
+/// Size estimates, large partitions and tombstone settings of `table`, from the system tables of the node answering.
+/// Figures a ScyllaDB version doesn't record are left unknown.
+pub(crate) async fn table_stats(session: &Session, table: &Name, migrations: Vec<String>) -> anyhow::Result<TableStats> {
+    let keyspace = table.keyspace.clone().unwrap_or_default();
+    let key = (keyspace.as_str(), table.name.as_str());
+    let mut stats = TableStats {
+        table: table.clone(),
+        migrations,
+        exists: false,
+        partitions: None,
+        bytes: None,
+        gc_grace_seconds: None,
+        default_ttl: None,
+        large_partitions: vec![],
+        tombstones: None,
+    };
+
+    let settings = session
+        .query_unpaged(
+            "SELECT gc_grace_seconds, default_time_to_live FROM system_schema.tables WHERE keyspace_name = ? AND table_name = ?",
+            key,
+        )
+        .await?
+        .rows
+        .unwrap_or_default();
+    let Some(row) = settings.first() else {
+        return Ok(stats);
+    };
+    stats.exists = true;
+    stats.gc_grace_seconds = row.columns.first().and_then(number);
+    stats.default_ttl = row.columns.get(1).and_then(number);
+
+    if let Ok(result) = session
+        .query_unpaged(
+            "SELECT partitions_count, mean_partition_size FROM system.size_estimates WHERE keyspace_name = ? AND table_name = ?",
+            key,
+        )
+        .await
+    {
+        let ranges: Vec<(i64, i64)> = result
+            .rows
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|row| Some((row.columns.first().and_then(number)?, row.columns.get(1).and_then(number)?)))
+            .collect();
+        if !ranges.is_empty() {
+            stats.partitions = Some(ranges.iter().map(|(count, _)| count).sum());
+            stats.bytes = Some(ranges.iter().map(|(count, mean)| count * mean).sum());
+        }
+    }
+
+    let large = session
+        .query_unpaged(
+            "SELECT partition_size, range_tombstones, dead_rows FROM system.large_partitions WHERE keyspace_name = ? AND table_name = ?",
+            key,
+        )
+        .await;
+    match large {
+        Ok(result) => {
+            let rows = result.rows.unwrap_or_default();
+            stats.large_partitions = rows.iter().filter_map(|row| row.columns.first().and_then(number)).collect();
+            let tombstones = rows.iter().flat_map(|row| row.columns.iter().skip(1).filter_map(number));
+            stats.tombstones = Some(tombstones.sum());
+        }
+        // versions without the tombstone columns
+        Err(_) => {
+            if let Ok(result) = session
+                .query_unpaged("SELECT partition_size FROM system.large_partitions WHERE keyspace_name = ? AND table_name = ?", key)
+                .await
+            {
+                let rows = result.rows.unwrap_or_default();
+                stats.large_partitions = rows.iter().filter_map(|row| row.columns.first().and_then(number)).collect();
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+fn number(value: &Option<CqlValue>) -> Option<i64> {
+    match value.as_ref()? {
+        CqlValue::BigInt(n) | CqlValue::Counter(scylla::frame::value::Counter(n)) => Some(*n),
+        CqlValue::Int(n) => Some(*n as i64),
+        _ => None,
+    }
+}
+
+/// Application tables with CDC enabled, found by their log table.
+pub(crate) async fn cdc_tables(session: &Session) -> anyhow::Result<Vec<Name>> {
+    let rows = session
+        .query_unpaged("SELECT keyspace_name, table_name FROM system_schema.tables", &[])
+        .await?
+        .rows_typed::<(String, String)>()?;
+
+    let mut tables = vec![];
+    for row in rows {
+        let (keyspace, name) = row?;
+        if let Some(base) = cdc::base_table(&Name { keyspace: Some(keyspace), name }) {
+            tables.push(base);
+        }
+    }
+
+    Ok(tables)
+}
+
+/// CQL definitions of `target` as the cluster sees it, in creation order, using the server side
+/// `DESCRIBE`. `target` is a keyspace, a qualified object name, or `None` for the whole schema.
+pub(crate) async fn describe(session: &Session, target: Option<&str>) -> anyhow::Result<Vec<String>> {
+    let query = match target {
+        None => "DESCRIBE SCHEMA".to_string(),
+        Some(target) if target.contains(';') => {
+            return Err(anyhow::anyhow!("Invalid object name: [{target}]"));
+        }
+        Some(target) if target.contains('.') => format!("DESCRIBE {target}"),
+        Some(keyspace) => format!("DESCRIBE KEYSPACE {keyspace}"),
+    };
+
+    let rows = session
+        .query_unpaged(query, &[])
+        .await?
+        .rows_typed::<(String, String, String, String)>()?;
+
+    let mut statements = vec![];
+    for row in rows {
+        let (_keyspace, _kind, _name, create_statement) = row?;
+        statements.push(create_statement);
+    }
+
+    Ok(statements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("").unwrap(), None);
+        assert_eq!(parse_duration("250ms").unwrap(), Some(Duration::from_millis(250)));
+        assert_eq!(parse_duration("30").unwrap(), Some(Duration::from_secs(30)));
+        assert_eq!(parse_duration("5m").unwrap(), Some(Duration::from_secs(300)));
+        assert_eq!(parse_duration("2h").unwrap(), Some(Duration::from_secs(7200)));
+        assert!(parse_duration("5d").is_err());
+        assert!(parse_duration("m").is_err());
+    }
+
+    #[test]
+    fn overflowing_durations() {
+        assert!(parse_duration(&format!("{}m", u64::MAX)).is_err());
+        assert!(parse_duration(&format!("{}h", u64::MAX / 60)).is_err());
+        assert!(parse_duration("99999999999999999999s").is_err());
+    }
+}
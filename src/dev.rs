@@ -0,0 +1,50 @@
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+
+/// Name of the container `dev up` starts, and `dev down` removes.
+pub const CONTAINER: &str = "scylladb-migrate-dev";
+pub const DEFAULT_IMAGE: &str = "scylladb/scylla";
+/// Address the container's CQL port is published on.
+pub const DB_URL: &str = "127.0.0.1:9042";
+/// A fresh single node takes a while to bootstrap, more on the first image pull.
+pub const READY_TIMEOUT: Duration = Duration::from_secs(180);
+
+/// Starts a disposable single node ScyllaDB in Docker, unless the container is already running.
+/// Returns whether it was started.
+pub async fn start(image: &str) -> anyhow::Result<bool> {
+    if docker(&["ps", "--quiet", "--filter", &format!("name=^{CONTAINER}$")]).await?.trim().is_empty() {
+        // a stopped leftover would keep the name taken
+        _ = docker(&["rm", "--force", CONTAINER]).await;
+        docker(&[
+            "run", "--detach", "--name", CONTAINER, "--publish", "9042:9042", image,
+            "--smp", "1", "--memory", "750M", "--overprovisioned", "1", "--developer-mode", "1",
+        ])
+        .await?;
+        return Ok(true);
+    }
+
+    Ok(false)
+}
+
+/// Removes the container, and its data with it.
+pub async fn stop() -> anyhow::Result<()> {
+    docker(&["rm", "--force", "--volumes", CONTAINER]).await?;
+    Ok(())
+}
+
+async fn docker(args: &[&str]) -> anyhow::Result<String> {
+    let output = Command::new("docker")
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(|e| anyhow::anyhow!("Unable to run docker: {e}"))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow::anyhow!("docker {} failed: {}", args.first().unwrap_or(&""), stderr.trim()));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
@@ -0,0 +1,142 @@
+use crate::cql::{self, Action, Name, ObjectKind};
+use crate::local;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+/// Kinds of objects compared between the migrations and the live schema.
+const TRACKED: [ObjectKind; 4] = [ObjectKind::Table, ObjectKind::Type, ObjectKind::Index, ObjectKind::MaterializedView];
+
+/// A difference between what the applied migrations define and what the cluster has.
+#[derive(Debug, Clone)]
+pub enum Drift {
+    /// Exists on the cluster, but no applied migration creates it.
+    Unmanaged { kind: ObjectKind, name: Name },
+    /// Created by an applied migration, but missing from the cluster.
+    Missing { kind: ObjectKind, name: Name, migration: String, statement: String },
+}
+
+impl Display for Drift {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Drift::Unmanaged { kind, name } => write!(f, "unmanaged: {kind} {name} exists but no applied migration creates it"),
+            Drift::Missing { kind, name, migration, .. } => {
+                write!(f, "missing: {kind} {name} created by [{migration}] does not exist")
+            }
+        }
+    }
+}
+
+/// Replays the `applied` migrations' up.cql to get the objects they should have left behind,
+/// and compares them with `live`. Objects with unqualified names only match by name.
+pub(crate) fn detect(dir_path: &str, applied: &[String], live: &[(ObjectKind, Name)]) -> Vec<Drift> {
+    let mut expected: BTreeMap<(ObjectKind, Name), (String, String)> = BTreeMap::new();
+
+    for migration in applied {
+        let Ok(up) = local::migration_file(&local::file_path(dir_path, migration, "up.cql")) else {
+            continue;
+        };
+
+        for statement in cql::parse_all(&up) {
+            let (Some(kind), Some(name)) = (statement.kind, statement.name.clone()) else {
+                continue;
+            };
+            if !TRACKED.contains(&kind) {
+                continue;
+            }
+
+            match statement.action {
+                Action::Create => {
+                    expected.insert((kind, name), (migration.clone(), statement.text.clone()));
+                }
+                Action::Drop => {
+                    expected.retain(|(k, n), _| !(*k == kind && n.name == name.name && same_keyspace(n, &name)));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let matches = |(kind, name): &(ObjectKind, Name), (other_kind, other): &(ObjectKind, Name)| {
+        kind == other_kind && name.name == other.name && same_keyspace(name, other)
+    };
+
+    let mut drifts: Vec<Drift> = live
+        .iter()
+        .filter(|object| TRACKED.contains(&object.0))
+        .filter(|object| !expected.keys().any(|e| matches(object, e)))
+        .map(|(kind, name)| Drift::Unmanaged { kind: *kind, name: name.clone() })
+        .collect();
+
+    for ((kind, name), (migration, statement)) in expected {
+        // without a keyspace the object can't be told apart from a namesake in another keyspace
+        if name.keyspace.is_none() {
+            continue;
+        }
+
+        if !live.iter().any(|object| matches(&(kind, name.clone()), object)) {
+            drifts.push(Drift::Missing { kind, name, migration, statement });
+        }
+    }
+
+    drifts
+}
+
+fn same_keyspace(a: &Name, b: &Name) -> bool {
+    a.keyspace.is_none() || b.keyspace.is_none() || a.keyspace == b.keyspace
+}
+
+/// Suggested corrective migration for `drifts`: missing objects are re-created from the
+/// migration that defined them, unmanaged objects are adopted from their live definition
+/// (`live_definition`) so other environments get them too.
+pub(crate) fn remediation(drifts: &[Drift], live_definition: &BTreeMap<Name, String>) -> (String, String) {
+    let mut up = vec![];
+    let mut down = vec![];
+
+    for drift in drifts {
+        match drift {
+            Drift::Missing { kind, name, statement, .. } => {
+                up.push(if_not_exists(statement));
+                down.push(drop_statement(*kind, name));
+            }
+            Drift::Unmanaged { kind, name } => {
+                if let Some(definition) = live_definition.get(name) {
+                    up.push(if_not_exists(definition.trim().trim_end_matches(';')));
+                    down.push(drop_statement(*kind, name));
+                }
+            }
+        }
+    }
+
+    down.reverse();
+    (join(up), join(down))
+}
+
+fn join(statements: Vec<String>) -> String {
+    statements.into_iter().map(|s| format!("{s};\n")).collect()
+}
+
+pub(crate) fn drop_statement(kind: ObjectKind, name: &Name) -> String {
+    let kind = match kind {
+        ObjectKind::MaterializedView => "MATERIALIZED VIEW".to_string(),
+        other => other.to_string().to_uppercase(),
+    };
+
+    format!("DROP {kind} IF EXISTS {}", name.quoted())
+}
+
+/// Makes a `CREATE` statement idempotent, so it is a no-op where the object already exists.
+fn if_not_exists(statement: &str) -> String {
+    let statement = statement.trim();
+    if cql::parse(statement).guarded {
+        return statement.to_string();
+    }
+
+    let upper = statement.to_uppercase();
+    for prefix in ["CREATE MATERIALIZED VIEW ", "CREATE CUSTOM INDEX ", "CREATE INDEX ", "CREATE TABLE ", "CREATE TYPE "] {
+        if upper.starts_with(prefix) {
+            return format!("{}IF NOT EXISTS {}", &statement[..prefix.len()], &statement[prefix.len()..]);
+        }
+    }
+
+    statement.to_string()
+}
@@ -0,0 +1,63 @@
+use std::fmt::{Display, Formatter};
+
+/// Where a failure happened.
+///
+/// Attached as context to the errors of a run, so it shows up in the printed error and
+/// can be read back with `anyhow::Error::downcast_ref::<ErrorContext>()`.
+#[derive(Debug, Clone, Default)]
+pub struct ErrorContext {
+    /// The config profile selected for the run, e.g. `prod`.
+    pub environment: Option<String>,
+    /// Contact point of the cluster the run targets.
+    pub cluster: String,
+    pub migration: Option<String>,
+    pub file: Option<String>,
+    /// 1-based index of the statement within `file`.
+    pub statement: Option<usize>,
+}
+
+impl ErrorContext {
+    pub fn new(cluster: &str) -> Self {
+        ErrorContext { cluster: cluster.to_string(), ..Default::default() }
+    }
+
+    pub fn environment(mut self, environment: &str) -> Self {
+        self.environment = Some(environment.to_string());
+        self
+    }
+
+    pub fn migration(mut self, migration: &str) -> Self {
+        self.migration = Some(migration.to_string());
+        self
+    }
+
+    pub fn file(mut self, file: &str) -> Self {
+        self.file = Some(file.to_string());
+        self
+    }
+
+    pub fn statement(mut self, index: usize) -> Self {
+        self.statement = Some(index);
+        self
+    }
+}
+
+impl Display for ErrorContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(environment) = &self.environment {
+            write!(f, "environment [{environment}], ")?;
+        }
+        write!(f, "cluster [{}]", self.cluster)?;
+        if let Some(migration) = &self.migration {
+            write!(f, ", migration [{migration}]")?;
+        }
+        if let Some(file) = &self.file {
+            write!(f, ", file [{file}]")?;
+        }
+        if let Some(statement) = self.statement {
+            write!(f, ", statement #{statement}")?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+use crate::cql::{self, Action, Risk, Statement};
+use crate::local;
+use anyhow::Result;
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+
+/// Human summary of what a migration does, for reviewers who don't read CQL fluently.
+#[derive(Debug)]
+pub struct Explanation {
+    pub migration: String,
+    pub statements: Vec<Statement>,
+    pub has_down: bool,
+}
+
+impl Explanation {
+    pub fn keyspaces(&self) -> BTreeSet<&str> {
+        self.statements.iter().filter_map(|s| s.keyspace()).collect()
+    }
+
+    /// Highest risk of any statement. A migration without a down is at least medium risk.
+    pub fn risk(&self) -> Risk {
+        let risk = self.statements.iter().map(|s| s.risk()).max().unwrap_or(Risk::Low);
+        if !self.has_down {
+            return risk.max(Risk::Medium);
+        }
+        risk
+    }
+
+    fn section(&self, f: &mut Formatter<'_>, title: &str, actions: &[Action]) -> std::fmt::Result {
+        let statements: Vec<&Statement> = self.statements
+            .iter()
+            .filter(|s| actions.contains(&s.action))
+            .collect();
+        if statements.is_empty() {
+            return Ok(());
+        }
+
+        writeln!(f, "{title}:")?;
+        for statement in statements {
+            writeln!(f, "    {} [{} risk]", statement.summary(), statement.risk())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Explanation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "migration: {}", self.migration)?;
+        writeln!(f, "statements: {}", self.statements.len())?;
+        self.section(f, "created", &[Action::Create])?;
+        self.section(f, "altered", &[Action::Alter])?;
+        self.section(f, "dropped", &[Action::Drop, Action::Truncate])?;
+        self.section(f, "data changes", &[Action::Insert, Action::Update, Action::Delete, Action::Batch])?;
+        self.section(f, "other", &[Action::Select, Action::Use, Action::Grant, Action::Revoke, Action::Other])?;
+
+        let keyspaces: Vec<&str> = self.keyspaces().into_iter().collect();
+        if keyspaces.is_empty() {
+            writeln!(f, "keyspaces touched: none named explicitly")?;
+        } else {
+            writeln!(f, "keyspaces touched: {}", keyspaces.join(", "))?;
+        }
+
+        writeln!(f, "down migration: {}", if self.has_down { "present" } else { "MISSING" })?;
+        write!(f, "risk: {}", self.risk())
+    }
+}
+
+/// Explains the up migration of `id`: its full directory name, a unique prefix or its name part.
+pub fn explain(dir_path: &str, id: &str) -> Result<Explanation> {
+    let migration = local::find(dir_path, id)?;
+    let up = local::migration_file(&local::file_path(dir_path, &migration, "up.cql"))?;
+    let has_down = local::has_migration_content(&local::file_path(dir_path, &migration, "down.cql"));
+
+    Ok(Explanation { migration, statements: cql::parse_all(&up), has_down })
+}
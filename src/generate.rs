@@ -0,0 +1,190 @@
+use crate::cql::{self, Name};
+use crate::db::Column;
+use std::collections::BTreeMap;
+
+/// How many rows to generate per table: a default, and overrides for some tables.
+#[derive(Debug, Clone)]
+pub struct RowCounts {
+    pub default: usize,
+    /// By `keyspace.table`.
+    pub tables: BTreeMap<String, usize>,
+}
+
+impl RowCounts {
+    /// Parses `100`, `app.users=5000` or a comma separated mix, e.g. `100,app.users=5000`.
+    /// When only overrides are given, other tables get no rows.
+    pub fn parse(value: &str) -> anyhow::Result<RowCounts> {
+        let mut counts = RowCounts { default: 0, tables: BTreeMap::new() };
+        let invalid = || anyhow::anyhow!("Invalid row counts: [{value}]. Use e.g. 100,app.users=5000");
+
+        for part in value.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            match part.split_once('=') {
+                Some((table, count)) => {
+                    counts.tables.insert(table.trim().to_lowercase(), count.trim().parse().map_err(|_| invalid())?);
+                }
+                None => counts.default = part.parse().map_err(|_| invalid())?,
+            }
+        }
+
+        Ok(counts)
+    }
+
+    pub fn get(&self, table: &Name) -> usize {
+        self.tables.get(&table.to_string()).copied().unwrap_or(self.default)
+    }
+}
+
+/// Small deterministic generator, good enough for test data.
+pub(crate) struct Random(u64);
+
+impl Random {
+    pub(crate) fn new(seed: u64) -> Random {
+        Random(seed)
+    }
+
+    // splitmix64
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n.max(1)
+    }
+
+    fn pick<'a>(&mut self, values: &[&'a str]) -> &'a str {
+        values[self.below(values.len() as u64) as usize]
+    }
+}
+
+const FIRST_NAMES: [&str; 10] = ["ada", "alan", "grace", "linus", "margaret", "dennis", "barbara", "ken", "radia", "edsger"];
+const LAST_NAMES: [&str; 8] = ["lovelace", "turing", "hopper", "torvalds", "hamilton", "ritchie", "liskov", "thompson"];
+const WORDS: [&str; 12] = ["alpha", "beta", "gamma", "delta", "river", "stone", "cloud", "ember", "maple", "orbit", "pixel", "quartz"];
+const COUNTRIES: [&str; 6] = ["DE", "FR", "IN", "JP", "US", "BR"];
+
+/// `INSERT` statements for `count` rows of `table`. `None` when the table can't be filled with
+/// inserts, i.e. counter tables.
+pub(crate) fn inserts(table: &Name, columns: &[&Column], count: usize, random: &mut Random) -> Option<Vec<String>> {
+    if columns.iter().any(|c| c.cql_type == "counter") {
+        return None;
+    }
+
+    let names: Vec<&str> = columns.iter().map(|c| c.name.as_str()).collect();
+    let names = names.iter().map(|n| cql::quote(n)).collect::<Vec<_>>().join(", ");
+
+    Some(
+        (0..count)
+            .map(|row| {
+                let values: Vec<String> = columns.iter().map(|c| value(&c.name, &c.cql_type, row, random)).collect();
+                format!("INSERT INTO {} ({names}) VALUES ({})", table.quoted(), values.join(", "))
+            })
+            .collect(),
+    )
+}
+
+/// A CQL literal for a column, picked by type and, for text, by what the column name suggests.
+/// `row` keeps keys of consecutive rows apart.
+fn value(column: &str, cql_type: &str, row: usize, random: &mut Random) -> String {
+    let cql_type = cql_type.trim();
+    if let Some(inner) = generic(cql_type, "frozen") {
+        return value(column, inner, row, random);
+    }
+    if let Some(inner) = generic(cql_type, "list") {
+        return format!("[{}]", collection(column, inner, random).join(", "));
+    }
+    if let Some(inner) = generic(cql_type, "set") {
+        return format!("{{{}}}", collection(column, inner, random).join(", "));
+    }
+    if let Some(inner) = generic(cql_type, "map") {
+        let (key, value_type) = split_pair(inner);
+        let entries: Vec<String> = (0..=random.below(3))
+            .map(|i| format!("{}: {}", value(column, key, i as usize, random), value(column, value_type, row, random)))
+            .collect();
+        return format!("{{{}}}", entries.join(", "));
+    }
+    if let Some(inner) = generic(cql_type, "tuple") {
+        let fields: Vec<String> = split_all(inner).into_iter().map(|t| value(column, t, row, random)).collect();
+        return format!("({})", fields.join(", "));
+    }
+
+    let n = random.next();
+    match cql_type {
+        "text" | "varchar" | "ascii" => format!("'{}'", text(column, row, random)),
+        "int" => ((n % 1_000_000) as i32).to_string(),
+        "bigint" | "varint" => ((n % 1_000_000_000_000) as i64).to_string(),
+        "smallint" => ((n % 30_000) as i16).to_string(),
+        "tinyint" => ((n % 120) as i8).to_string(),
+        "float" | "double" | "decimal" => format!("{:.2}", (n % 1_000_000) as f64 / 100.0),
+        "boolean" => (n & 1 == 0).to_string(),
+        "uuid" => "uuid()".to_string(),
+        "timeuuid" => "now()".to_string(),
+        // within the last year
+        "timestamp" => format!("{}", chrono::Utc::now().timestamp_millis() - (n % 31_536_000_000) as i64),
+        "date" => format!("'{}'", (chrono::Utc::now() - chrono::Duration::days((n % 365) as i64)).format("%Y-%m-%d")),
+        "time" => format!("'{:02}:{:02}:{:02}'", n % 24, (n >> 8) % 60, (n >> 16) % 60),
+        "duration" => format!("{}m", n % 600),
+        "inet" => format!("'10.{}.{}.{}'", n % 256, (n >> 8) % 256, (n >> 16) % 256),
+        "blob" => format!("0x{:016x}", n),
+        // a user defined type: left empty
+        _ => "null".to_string(),
+    }
+}
+
+pub(crate) fn text(column: &str, row: usize, random: &mut Random) -> String {
+    let first = random.pick(&FIRST_NAMES);
+    let last = random.pick(&LAST_NAMES);
+
+    if column.contains("email") {
+        format!("{first}.{last}{row}@example.com")
+    } else if column.contains("first") {
+        first.to_string()
+    } else if column.contains("last") || column.contains("surname") {
+        last.to_string()
+    } else if column.contains("name") {
+        format!("{first} {last}")
+    } else if column.contains("phone") {
+        format!("+1555{:07}", random.below(10_000_000))
+    } else if column.contains("country") {
+        random.pick(&COUNTRIES).to_string()
+    } else if column.contains("url") {
+        format!("https://example.com/{}/{row}", random.pick(&WORDS))
+    } else {
+        format!("{}-{}-{row}", random.pick(&WORDS), random.pick(&WORDS))
+    }
+}
+
+fn collection(column: &str, inner: &str, random: &mut Random) -> Vec<String> {
+    (0..=random.below(3)).map(|i| value(column, inner, i as usize, random)).collect()
+}
+
+/// `inner` of `name<inner>`.
+fn generic<'a>(cql_type: &'a str, name: &str) -> Option<&'a str> {
+    cql_type.strip_prefix(name)?.trim_start().strip_prefix('<')?.strip_suffix('>')
+}
+
+fn split_pair(types: &str) -> (&str, &str) {
+    let all = split_all(types);
+    (all.first().copied().unwrap_or("text"), all.get(1).copied().unwrap_or("text"))
+}
+
+/// Splits a type list on the commas outside of angle brackets.
+fn split_all(types: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in types.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(types[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(types[start..].trim());
+    parts
+}
@@ -0,0 +1,140 @@
+use crate::cql::{self, Action, Name, ObjectKind, Token};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Dot,
+    Mermaid,
+}
+
+impl Format {
+    pub fn parse(value: &str) -> anyhow::Result<Format> {
+        match value {
+            "" | "dot" => Ok(Format::Dot),
+            "mermaid" => Ok(Format::Mermaid),
+            _ => Err(anyhow::anyhow!("Unknown graph format: [{value}]. Use dot or mermaid")),
+        }
+    }
+}
+
+/// Keyspaces, their tables, types and views, and how they relate.
+#[derive(Debug, Default)]
+pub struct SchemaGraph {
+    objects: BTreeMap<String, BTreeSet<(ObjectKind, String)>>,
+    /// (from, to, label)
+    edges: BTreeSet<(Name, Name, &'static str)>,
+}
+
+impl SchemaGraph {
+    /// Builds the graph from CQL definitions, like the output of `describe` or a schema dump.
+    pub fn from_cql(schema: &str) -> SchemaGraph {
+        let statements: Vec<_> = cql::parse_all(schema)
+            .into_iter()
+            .filter(|s| s.action == Action::Create)
+            .collect();
+
+        let mut graph = SchemaGraph::default();
+        let mut types: BTreeSet<Name> = BTreeSet::new();
+
+        for statement in &statements {
+            let (Some(kind), Some(name)) = (statement.kind, &statement.name) else {
+                continue;
+            };
+
+            match kind {
+                ObjectKind::Keyspace => {
+                    graph.objects.entry(name.name.clone()).or_default();
+                }
+                ObjectKind::Table | ObjectKind::Type | ObjectKind::MaterializedView => {
+                    let keyspace = name.keyspace.clone().unwrap_or_default();
+                    graph.objects.entry(keyspace).or_default().insert((kind, name.name.clone()));
+                    if kind == ObjectKind::Type {
+                        types.insert(name.clone());
+                    }
+                }
+                _ => {}
+            }
+
+            if let (ObjectKind::MaterializedView, Some(base)) = (kind, &statement.base) {
+                graph.edges.insert((name.clone(), base.clone(), "view of"));
+            }
+        }
+
+        // a column type or a field type naming a UDT of the same keyspace is a usage
+        for statement in &statements {
+            let (Some(kind @ (ObjectKind::Table | ObjectKind::Type)), Some(name)) = (statement.kind, &statement.name) else {
+                continue;
+            };
+
+            for token in cql::tokenize(&statement.text).iter().skip(3) {
+                let Token::Word(word) = token else {
+                    continue;
+                };
+                let used = Name { keyspace: name.keyspace.clone(), name: word.to_lowercase() };
+                if types.contains(&used) && !(kind == ObjectKind::Type && used == *name) {
+                    graph.edges.insert((name.clone(), used, "uses"));
+                }
+            }
+        }
+
+        graph
+    }
+
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Dot => self.dot(),
+            Format::Mermaid => self.mermaid(),
+        }
+    }
+
+    fn dot(&self) -> String {
+        let mut out = String::from("digraph schema {\n    rankdir=LR;\n    node [shape=box];\n");
+
+        for (keyspace, objects) in &self.objects {
+            out.push_str(&format!("    subgraph \"cluster_{keyspace}\" {{\n        label=\"{keyspace}\";\n"));
+            for (kind, name) in objects {
+                let shape = match kind {
+                    ObjectKind::Type => "component",
+                    ObjectKind::MaterializedView => "box3d",
+                    _ => "box",
+                };
+                out.push_str(&format!(
+                    "        \"{keyspace}.{name}\" [label=\"{name}\\n({kind})\", shape={shape}];\n"
+                ));
+            }
+            out.push_str("    }\n");
+        }
+
+        for (from, to, label) in &self.edges {
+            out.push_str(&format!("    \"{from}\" -> \"{to}\" [label=\"{label}\"];\n"));
+        }
+
+        out.push('}');
+        out
+    }
+
+    fn mermaid(&self) -> String {
+        let id = |name: &Name| name.to_string().replace(['.', '"'], "_");
+        let mut out = String::from("flowchart LR\n");
+
+        for (keyspace, objects) in &self.objects {
+            out.push_str(&format!("    subgraph {keyspace}\n"));
+            for (kind, name) in objects {
+                let node = id(&Name { keyspace: Some(keyspace.clone()), name: name.clone() });
+                let label = format!("{name}<br/>({kind})");
+                match kind {
+                    ObjectKind::Type => out.push_str(&format!("        {node}([\"{label}\"])\n")),
+                    ObjectKind::MaterializedView => out.push_str(&format!("        {node}[[\"{label}\"]]\n")),
+                    _ => out.push_str(&format!("        {node}[\"{label}\"]\n")),
+                }
+            }
+            out.push_str("    end\n");
+        }
+
+        for (from, to, label) in &self.edges {
+            out.push_str(&format!("    {} -->|{label}| {}\n", id(from), id(to)));
+        }
+
+        out.trim_end().to_string()
+    }
+}
@@ -0,0 +1,55 @@
+use crate::cql::{self, Action, Name, ObjectKind};
+use crate::local;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+/// A schema object of the application keyspaces.
+#[derive(Debug, Clone)]
+pub struct InventoryItem {
+    pub kind: ObjectKind,
+    pub name: Name,
+    /// Last applied migration whose up.cql creates or alters the object.
+    pub last_migration: Option<String>,
+}
+
+impl Display for InventoryItem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let migration = self.last_migration.as_deref().unwrap_or("unmanaged");
+        write!(f, "{:<18} {:<50} {migration}", self.kind.to_string(), self.name.to_string())
+    }
+}
+
+/// Annotates `objects` with the last of the `applied` migrations touching them,
+/// by parsing the local up.cql of each applied migration.
+pub(crate) fn annotate(dir_path: &str, applied: &[String], objects: Vec<(ObjectKind, Name)>) -> Vec<InventoryItem> {
+    let mut touched: HashMap<(ObjectKind, Name), String> = HashMap::new();
+
+    for migration in applied {
+        let Ok(up) = local::migration_file(&local::file_path(dir_path, migration, "up.cql")) else {
+            continue;
+        };
+
+        for statement in cql::parse_all(&up) {
+            if !matches!(statement.action, Action::Create | Action::Alter) {
+                continue;
+            }
+            if let (Some(kind), Some(name)) = (statement.kind, statement.name) {
+                touched.insert((kind, name), migration.clone());
+            }
+        }
+    }
+
+    objects
+        .into_iter()
+        .map(|(kind, name)| {
+            // unqualified names in migrations without a USE can't be tied to a keyspace
+            let unqualified = Name { keyspace: None, name: name.name.clone() };
+            let last_migration = touched
+                .get(&(kind, name.clone()))
+                .or_else(|| touched.get(&(kind, unqualified)))
+                .cloned();
+
+            InventoryItem { kind, name, last_migration }
+        })
+        .collect()
+}
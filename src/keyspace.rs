@@ -0,0 +1,103 @@
+use crate::cql::{Action, ObjectKind, Statement};
+use crate::db;
+use std::fmt::{Display, Formatter};
+
+/// A statement referencing a keyspace that is likely a typo.
+#[derive(Debug, Clone)]
+pub struct KeyspaceReference {
+    pub migration: String,
+    pub summary: String,
+    pub keyspace: String,
+    /// Not one of the configured target keyspaces, as opposed to not existing.
+    pub untargeted: bool,
+    /// A known keyspace with a close name.
+    pub suggestion: Option<String>,
+}
+
+impl KeyspaceReference {
+    /// What is wrong, without the migration.
+    pub fn message(&self) -> String {
+        let problem = if self.untargeted { "is not a target keyspace" } else { "does not exist and is not created before" };
+        let mut message = format!("{}: keyspace {} {problem}", self.summary, self.keyspace);
+        if let Some(suggestion) = &self.suggestion {
+            message.push_str(&format!(", did you mean {suggestion}?"));
+        }
+        message
+    }
+}
+
+impl Display for KeyspaceReference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}", self.migration, self.message())
+    }
+}
+
+/// Parses a comma separated list of target keyspaces.
+pub fn parse_targets(value: &str) -> Vec<String> {
+    value.split(',').map(|k| k.trim().to_string()).filter(|k| !k.is_empty()).collect()
+}
+
+/// Checks the keyspaces `migrations` reference, in order, against `targets`, when any are configured,
+/// and against the keyspaces that `existing` on the cluster or are created by an earlier statement, when known.
+pub(crate) fn check(migrations: &[(String, Vec<Statement>)], targets: &[String], existing: Option<&[String]>) -> Vec<KeyspaceReference> {
+    let mut known: Vec<String> = existing.unwrap_or_default().to_vec();
+    let mut references = vec![];
+
+    for (migration, statements) in migrations {
+        for statement in statements {
+            let Some(keyspace) = statement.keyspace().map(str::to_string) else {
+                continue;
+            };
+            if keyspace.starts_with("system") || keyspace == db::keyspace() {
+                continue;
+            }
+
+            let creates = statement.action == Action::Create && statement.kind == Some(ObjectKind::Keyspace);
+            let untargeted = !targets.is_empty() && !targets.contains(&keyspace);
+            let missing = existing.is_some() && !creates && !known.contains(&keyspace);
+            if untargeted || missing {
+                let candidates = targets.iter().chain(&known);
+                references.push(KeyspaceReference {
+                    migration: migration.clone(),
+                    summary: statement.summary(),
+                    suggestion: closest(&keyspace, candidates),
+                    keyspace: keyspace.clone(),
+                    untargeted,
+                });
+            }
+
+            if creates {
+                known.push(keyspace);
+            }
+        }
+    }
+
+    references
+}
+
+/// The candidate within two edits of `keyspace`, if any.
+fn closest<'a>(keyspace: &str, candidates: impl Iterator<Item = &'a String>) -> Option<String> {
+    candidates
+        .filter(|candidate| *candidate != keyspace)
+        .map(|candidate| (distance(keyspace, candidate), candidate))
+        .filter(|(distance, _)| *distance <= 2)
+        .min()
+        .map(|(_, candidate)| candidate.clone())
+}
+
+/// Levenshtein distance.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
@@ -0,0 +1,45 @@
+pub mod anonymize;
+pub mod astra;
+pub mod auth;
+pub mod bulk;
+pub mod cdc;
+pub mod config;
+pub mod conflict;
+pub mod cql;
+pub mod db;
+pub mod dev;
+pub mod drift;
+mod error;
+pub mod explain;
+pub mod generate;
+pub mod graph;
+pub mod inventory;
+pub mod keyspace;
+pub mod lint;
+pub mod local;
+mod migrator;
+pub mod plan;
+pub mod progress;
+pub mod proxy;
+pub mod rollback;
+pub mod scan;
+pub mod snapshot;
+pub mod stats;
+pub mod status;
+mod table;
+pub mod template;
+pub mod tls;
+pub mod update;
+pub mod validate;
+
+pub use bulk::BulkOptions;
+pub use cdc::CdcCheck;
+pub use conflict::{Conflict, ConflictResolver, Resolution};
+pub use db::{ConnectOptions, ReconnectPolicy, RetryPolicy};
+pub use error::ErrorContext;
+pub use migrator::{Cancelled, Migrator, PlannedMigration, Repair, Revert};
+pub use rollback::{DownFile, RollbackPlan};
+pub use scan::ScanOptions;
+pub use snapshot::{Downgrade, Snapshot};
+pub use progress::{Observer, Progress};
+pub use tokio_util::sync::CancellationToken;
@@ -0,0 +1,303 @@
+use crate::cql::{self, Name, Token};
+use crate::keyspace;
+use crate::local;
+use crate::rollback::{self, DownFile};
+use crate::table::{self, Table};
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// Something wrong with a local migration, found without connecting to the database.
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub migration: String,
+    /// Name of the rule, for disabling it.
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}: {} ({})", self.migration, self.severity, self.message, self.rule)
+    }
+}
+
+/// Rules `lint` skips, for projects whose guidance differs.
+#[derive(Debug, Clone, Default)]
+pub struct LintOptions {
+    pub disabled: Vec<String>,
+    /// Keyspaces the migrations are meant for. Any other one is reported, when set.
+    pub keyspaces: Vec<String>,
+}
+
+impl LintOptions {
+    /// Parses a comma separated list of rule names.
+    pub fn parse(disabled: &str) -> LintOptions {
+        LintOptions { disabled: disabled.split(',').map(|r| r.trim().to_string()).filter(|r| !r.is_empty()).collect(), keyspaces: vec![] }
+    }
+}
+
+/// Lints every migration in `dir_path`. Only errors should fail a build.
+pub fn lint(dir_path: &str, options: &LintOptions) -> Result<Vec<Finding>> {
+    let migrations = local::subdirectories(dir_path)?;
+    let mut findings = vec![];
+
+    // an option set by a later ALTER TABLE counts for the table
+    let mut altered: BTreeMap<Name, Vec<String>> = BTreeMap::new();
+    let mut tables = vec![];
+    let mut columns = vec![];
+    let mut statements = vec![];
+    for migration in &migrations {
+        let Ok(up) = local::migration_file(&local::file_path(dir_path, migration, "up.cql")) else {
+            continue;
+        };
+        for statement in cql::parse_all(&up) {
+            statements.push((migration.clone(), statement.clone()));
+            if let Some(name) = &statement.name {
+                let options = table::altered_options(&statement).into_iter().map(|(option, _)| option);
+                altered.entry(name.clone()).or_default().extend(options);
+            }
+            if let Some(table) = table::parse(&statement) {
+                for column in &table.columns {
+                    columns.push((migration.clone(), table.name.clone(), column.clone(), statement.text.clone()));
+                }
+                tables.push((migration.clone(), table));
+            }
+            for column in table::added_columns(&statement) {
+                if let Some(name) = &statement.name {
+                    columns.push((migration.clone(), name.clone(), column, statement.text.clone()));
+                }
+            }
+        }
+    }
+
+    for env in local::seed_envs(dir_path)? {
+        for seed in local::seeds(dir_path, &env)? {
+            let Ok(contents) = local::file_contents(&local::seed_path(dir_path, &env, &seed)) else {
+                continue;
+            };
+            let label = format!("{}/{env}/{seed}", local::SEEDS_DIR);
+            statements.extend(cql::parse_all(&contents).into_iter().map(|statement| (label.clone(), statement)));
+        }
+    }
+
+    for migration in &migrations {
+        let mut finding = |rule, severity, message: String| {
+            findings.push(Finding { migration: migration.clone(), rule, severity, message })
+        };
+
+        match rollback::down_file(dir_path, migration) {
+            DownFile::Present => {}
+            DownFile::Missing | DownFile::Empty => finding(
+                "down",
+                Severity::Error,
+                format!("requires a non-empty down.cql, or `-- {}: true` in up.cql", local::NO_DOWN),
+            ),
+            DownFile::Irreversible => {
+                if local::has_migration_content(&local::file_path(dir_path, migration, "down.cql")) {
+                    finding("down", Severity::Error, format!("declares {} but has a down.cql, which is never executed", local::NO_DOWN));
+                }
+            }
+        }
+        for part in local::unmatched_parts(dir_path, migration) {
+            finding("down", Severity::Error, format!("{part} has no matching up or down part"));
+        }
+        for file in ["up.cql", "down.cql"] {
+            let path = local::file_path(dir_path, migration, file);
+            if local::parts(&path).is_empty() {
+                continue;
+            }
+            if let Err(e) = local::migration_directives(&path) {
+                finding("directive", Severity::Error, format!("{file}: {e}"));
+            }
+        }
+    }
+
+    for (migration, table) in &tables {
+        let altered = altered.get(&table.name).map(Vec::as_slice).unwrap_or_default();
+        let mut finding = |rule, severity, message: String| {
+            findings.push(Finding { migration: migration.clone(), rule, severity, message })
+        };
+
+        if table.is_time_series() {
+            if !is_twcs(table) && !altered.iter().any(|o| o == "compaction") {
+                finding("twcs", Severity::Warning, format!("{} is clustered by time but does not use TimeWindowCompactionStrategy", table.name));
+            }
+            if !has_ttl(table) && !altered.iter().any(|o| o == "default_time_to_live") {
+                finding("ttl", Severity::Warning, format!("{} is clustered by time but has no default_time_to_live", table.name));
+            }
+        }
+    }
+
+    let mut by_file: Vec<(String, Vec<cql::Statement>)> = vec![];
+    for (migration, statement) in &statements {
+        match by_file.last_mut() {
+            Some((last, file)) if last == migration => file.push(statement.clone()),
+            _ => by_file.push((migration.clone(), vec![statement.clone()])),
+        }
+    }
+    for reference in keyspace::check(&by_file, &options.keyspaces, None) {
+        findings.push(Finding { message: reference.message(), migration: reference.migration, rule: "keyspace", severity: Severity::Error });
+    }
+
+    for (migration, statement) in &statements {
+        for identifier in table::defined_identifiers(statement) {
+            let Token::Word(word) = identifier else {
+                continue;
+            };
+            let (severity, message) = if cql::is_reserved(&word) {
+                (Severity::Error, format!("{}: {word} is a reserved keyword, quote it as {}", statement.summary(), cql::quote(&word.to_lowercase())))
+            } else if word.chars().any(|c| c.is_ascii_uppercase()) && word.chars().any(|c| c.is_ascii_lowercase()) {
+                let folded = word.to_lowercase();
+                (Severity::Warning, format!("{}: unquoted {word} is folded to {folded}, quote it as \"{word}\" to keep the case", statement.summary()))
+            } else if word.starts_with(|c: char| c.is_ascii_digit()) {
+                (Severity::Error, format!("{}: {word} starts with a digit, quote it as \"{word}\"", statement.summary()))
+            } else {
+                continue;
+            };
+            findings.push(Finding { migration: migration.clone(), rule: "identifier", severity, message });
+        }
+    }
+
+    // Scylla only rejects these when applying them
+    let counters: Vec<Name> = tables.iter().filter(|(_, t)| is_counter(t)).map(|(_, t)| t.name.clone()).collect();
+    let counter_table = |name: &Name| counters.iter().any(|c| c.name == name.name && (c.keyspace.is_none() || name.keyspace.is_none() || c.keyspace == name.keyspace));
+    for (migration, table) in tables.iter().filter(|(_, t)| is_counter(t)) {
+        let mut finding = |message: String| {
+            findings.push(Finding { migration: migration.clone(), rule: "counter", severity: Severity::Error, message })
+        };
+
+        for column in &table.columns {
+            let in_key = table.partition_key.contains(&column.name) || table.clustering.contains(&column.name);
+            match (in_key, column.cql_type == "counter") {
+                (true, true) => finding(format!("{}.{} is a counter in the primary key", table.name, column.name)),
+                (false, false) => finding(format!("{} mixes counters with the regular column {}", table.name, column.name)),
+                _ => {}
+            }
+        }
+        if has_ttl(table) {
+            finding(format!("{} is a counter table, which can't have a default_time_to_live", table.name));
+        }
+    }
+    for (migration, statement) in &statements {
+        let Some(name) = &statement.name else {
+            continue;
+        };
+        let counter = counter_table(name);
+        let mut finding = |message: String| {
+            findings.push(Finding { migration: migration.clone(), rule: "counter", severity: Severity::Error, message })
+        };
+
+        for column in table::added_columns(statement) {
+            match (counter, column.cql_type == "counter") {
+                (true, false) => finding(format!("adds the regular column {} to the counter table {name}", column.name)),
+                (false, true) => finding(format!("adds the counter {} to {name}, which has regular columns", column.name)),
+                _ => {}
+            }
+        }
+        if !counter {
+            continue;
+        }
+        match statement.action {
+            cql::Action::Insert => finding(format!("inserts into the counter table {name}, counters can only be updated")),
+            cql::Action::Update if uses_ttl(&statement.text) => finding(format!("sets a TTL on the counter table {name}")),
+            _ => {}
+        }
+    }
+
+    let mut defined: BTreeMap<Name, Vec<table::Column>> = BTreeMap::new();
+    for (_, table, column, _) in &columns {
+        defined.entry(table.clone()).or_default().push(column.clone());
+    }
+    for (migration, statement) in &statements {
+        let (Some(column), Some(base)) = (table::indexed_column(statement), &statement.base) else {
+            continue;
+        };
+        let cql_type = defined.get(base).and_then(|columns| columns.iter().find(|c| c.name == column)).map(|c| c.cql_type.as_str());
+        let hints = local::frontmatter(&statement.text);
+        let hint = |key: &str, value: &str| hints.iter().any(|(k, v)| k == key && v.eq_ignore_ascii_case(value));
+
+        let reason = if hint("cardinality", "high") || (!hint("cardinality", "low") && looks_high_cardinality(&column, cql_type)) {
+            "looks high cardinality"
+        } else if hint("updates", "frequent") || (!hint("updates", "rare") && looks_frequently_updated(&column)) {
+            "looks frequently updated"
+        } else {
+            continue;
+        };
+        findings.push(Finding {
+            migration: migration.clone(),
+            rule: "index",
+            severity: Severity::Warning,
+            message: format!(
+                "secondary index on {base}.{column}, which {reason}. A materialized view or a table keyed by {column} usually serves \
+                 the query better, see \"Secondary indexes and materialized views\" in the README. Otherwise say so with `-- cardinality: low` or `-- updates: rare` above it"
+            ),
+        });
+    }
+
+    // values of these grow without bound unless the application keeps them small, which deserves a comment
+    for (migration, table, column, text) in &columns {
+        if table::has_comment(text, &column.name) {
+            continue;
+        }
+        let (rule, message) = if column.is_unbounded_collection() {
+            ("collection", format!("{table}.{} is a non-frozen {}, prone to oversized cells. Bound it, or justify it in a comment", column.name, column.cql_type))
+        } else if column.is_blob() {
+            ("blob", format!("{table}.{} is a blob, prone to oversized cells. Justify its size in a comment", column.name))
+        } else {
+            continue;
+        };
+        findings.push(Finding { migration: migration.clone(), rule, severity: Severity::Warning, message });
+    }
+
+    findings.retain(|f| !options.disabled.iter().any(|rule| rule == f.rule));
+    findings.sort_by(|a, b| a.migration.cmp(&b.migration));
+    Ok(findings)
+}
+
+fn is_counter(table: &Table) -> bool {
+    table.columns.iter().any(|c| c.cql_type == "counter")
+}
+
+fn uses_ttl(text: &str) -> bool {
+    let tokens = cql::tokenize(text);
+    tokens.windows(2).any(|w| matches!(w, [Token::Word(using), Token::Word(ttl)] if using.eq_ignore_ascii_case("USING") && ttl.eq_ignore_ascii_case("TTL")))
+}
+
+/// Mostly distinct values: each index entry then points to a handful of rows scattered across the cluster.
+fn looks_high_cardinality(column: &str, cql_type: Option<&str>) -> bool {
+    let by_name = column.split('_').any(|part| ["id", "email", "uuid", "token", "phone"].contains(&part));
+    by_name || matches!(cql_type, Some("uuid" | "timeuuid" | "timestamp"))
+}
+
+/// Every update of the column is also a delete and an insert in the index.
+fn looks_frequently_updated(column: &str) -> bool {
+    column.split('_').any(|part| ["updated", "modified", "last", "status", "count", "seen"].contains(&part))
+}
+
+fn is_twcs(table: &Table) -> bool {
+    let compaction = table.option("compaction").unwrap_or_default();
+    compaction.iter().any(|t| matches!(t, Token::Literal(class) if class.ends_with("TimeWindowCompactionStrategy")))
+}
+
+fn has_ttl(table: &Table) -> bool {
+    matches!(table.option("default_time_to_live"), Some([Token::Word(ttl)]) if ttl != "0")
+}
@@ -0,0 +1,578 @@
+use anyhow::Result;
+use scylla::statement::Consistency;
+use std::collections::BTreeMap;
+use std::fs::{create_dir, read_dir, read_to_string, write};
+use std::path::Path;
+use std::time::Duration;
+
+/// Directory of seed fixtures inside the migrations directory, one subdirectory per environment.
+pub const SEEDS_DIR: &str = "seeds";
+
+/// Directory of user templates for `generate --template` inside the migrations directory, one subdirectory per template.
+pub const TEMPLATES_DIR: &str = "templates";
+
+/// Frontmatter key declaring that a migration cannot be reverted.
+pub const NO_DOWN: &str = "no_down";
+
+/// Frontmatter key listing the migrations a squashed migration replaces, comma separated.
+pub const SQUASHES: &str = "squashes";
+
+/// Frontmatter key describing what a migration does, recorded in the tracker.
+pub const DESCRIPTION: &str = "description";
+
+/// Comment line starting the up statements of a single file migration.
+pub const UP_MARKER: &str = "-- migrate:up";
+
+/// Comment line starting the down statements of a single file migration.
+pub const DOWN_MARKER: &str = "-- migrate:down";
+
+/// Prefix of the directive comment lines at the top of a migration file, e.g. `-- migrate:timeout=120s`.
+pub const DIRECTIVE_PREFIX: &str = "-- migrate:";
+
+/// Suffix of the down part undoing the up part of the same name, e.g. `001_tables.down.cql` for `001_tables.cql`.
+pub const DOWN_PART_SUFFIX: &str = ".down.cql";
+
+/// Prefix of the env vars giving the value of a `${NAME}` placeholder, e.g. `SCYLLADB_MIGRATE_VAR_KEYSPACE`.
+pub const VAR_PREFIX: &str = "SCYLLADB_MIGRATE_VAR_";
+
+/// Creates a new migration directory named after the current time and `name`, with the
+/// given up.cql and down.cql contents. Without down contents no down.cql is written.
+/// Returns the migration id.
+pub fn create(dir_path: &str, name: &str, up_contents: &str, down_contents: Option<&str>) -> Result<String> {
+    let date = chrono::Local::now();
+    let formatted = date.format("%Y-%m-%d-%H%M%S");
+    let migration = format!("{formatted}_{name}");
+    write_migration(dir_path, &migration, up_contents, down_contents)?;
+
+    Ok(migration)
+}
+
+/// Like `create`, for a migration with the given id.
+pub(crate) fn write_migration(dir_path: &str, migration: &str, up_contents: &str, down_contents: Option<&str>) -> Result<()> {
+    let subdirectory_path = format!("{dir_path}/{migration}");
+    let subdirectory_path = subdirectory_path.as_str();
+
+    let up = format!("{subdirectory_path}/up.cql");
+    let down = format!("{subdirectory_path}/down.cql");
+
+    let dir = Path::new(dir_path);
+    if !dir.is_dir() {
+        return Err(anyhow::anyhow!("Not a directory, or does not exist: [{dir_path}]"));
+    }
+
+    create_dir(subdirectory_path)?;
+    write(up, up_contents)?;
+    if let Some(down_contents) = down_contents {
+        write(down, down_contents)?;
+    }
+
+    Ok(())
+}
+
+/// Names of the migrations in `dir_path`, sorted, which is also the apply order. A migration is either a directory,
+/// or a single `.cql` file holding both its up and down statements, named after the file without the extension.
+/// Such a file is only taken for a migration when named like `create` names them, or when it has a `-- migrate:up`
+/// line, so a stray script next to the migrations isn't applied.
+pub(crate) fn subdirectories(dir_path: &str) -> Result<Vec<String>> {
+    let entries = read_dir(dir_path)?;
+
+    let mut subdirectories: Vec<String> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let path = entry.path();
+            if path.is_file() && path.extension()? == "cql" {
+                let migration = path.file_stem()?.to_str()?.to_string();
+                let marked = || read_to_string(&path).is_ok_and(|contents| contents.lines().any(|line| line.trim() == UP_MARKER));
+                return (is_well_formed(&migration) || marked()).then_some(migration);
+            }
+            if !path.is_dir() || path.file_name()? == SEEDS_DIR || path.file_name()? == TEMPLATES_DIR {
+                return None;
+            }
+
+            let filename = path.file_name()?
+                .to_str()
+                .unwrap()
+                .to_string();
+            Some(filename)
+        })
+        .collect();
+
+    subdirectories.sort();
+    // a directory shadows the single file of the same name
+    subdirectories.dedup();
+
+    Ok(subdirectories)
+}
+
+/// Whether `migration` is named like `create` names them: `YYYY-MM-DD-HHMMSS_name`.
+pub(crate) fn is_well_formed(migration: &str) -> bool {
+    let Some((timestamp, name)) = migration.split_once('_') else {
+        return false;
+    };
+
+    !name.is_empty() && chrono::NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d-%H%M%S").is_ok()
+}
+
+/// Resolves `id` to a local migration. Besides the full directory name, a unique prefix
+/// (e.g. the timestamp) or the name part after the timestamp is accepted.
+pub(crate) fn find(dir_path: &str, id: &str) -> Result<String> {
+    let migrations = subdirectories(dir_path)?;
+    if migrations.iter().any(|m| m == id) {
+        return Ok(id.to_string());
+    }
+
+    let matches: Vec<&String> = migrations
+        .iter()
+        .filter(|m| m.starts_with(id) || m.split_once('_').is_some_and(|(_, name)| name == id))
+        .collect();
+
+    match matches.as_slice() {
+        [migration] => Ok(migration.to_string()),
+        [] => Err(anyhow::anyhow!("No migration matching [{id}] in [{dir_path}]")),
+        _ => Err(anyhow::anyhow!("Migration id [{id}] is ambiguous: {matches:?}")),
+    }
+}
+
+pub(crate) fn file_path(dir_path: &str, migration: &str, file: &str) -> String {
+    format!("{dir_path}/{migration}/{file}")
+}
+
+/// Files making up the up.cql or down.cql at `path`, in apply order. Instead of a single up.cql, a migration may
+/// spread its statements over `.cql` parts, e.g. `001_tables.cql` and `002_indexes.cql`, applied in lexical order.
+/// Each is undone by its `.down.cql` match, e.g. `001_tables.down.cql`, these going in reverse order. A single file
+/// migration is the only part of both, when it has a down section. Any other existing file is its only part. Empty
+/// when there is neither. Read parts with `part_contents`.
+pub(crate) fn parts(path: &str) -> Vec<String> {
+    if Path::new(path).is_file() {
+        return vec![path.to_string()];
+    }
+    let Some((dir, name)) = path.rsplit_once('/').filter(|(_, name)| *name == "up.cql" || *name == "down.cql") else {
+        return vec![];
+    };
+    let down = name == "down.cql";
+    let single = format!("{dir}.cql");
+    if !Path::new(dir).is_dir() && Path::new(&single).is_file() {
+        let has_down = file_contents(&single).is_ok_and(|contents| sections(&contents).1.is_some());
+        return if !down || has_down { vec![single] } else { vec![] };
+    }
+    let Ok(entries) = read_dir(dir) else {
+        return vec![];
+    };
+
+    let mut parts: Vec<String> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_str()?;
+            let part = path.is_file() && name.ends_with(".cql") && name != "up.cql" && name != "down.cql";
+            (part && name.ends_with(DOWN_PART_SUFFIX) == down).then(|| format!("{dir}/{name}"))
+        })
+        .collect();
+
+    parts.sort();
+    if down {
+        parts.reverse();
+    }
+    parts
+}
+
+/// File names of the up parts of `migration` without their down part, and down parts without their up part.
+/// Parts without any down part are left to the checks of a missing down.cql.
+pub(crate) fn unmatched_parts(dir_path: &str, migration: &str) -> Vec<String> {
+    let up = file_path(dir_path, migration, "up.cql");
+    let down = file_path(dir_path, migration, "down.cql");
+    let single_file = !Path::new(&format!("{dir_path}/{migration}")).is_dir();
+    if single_file || Path::new(&up).is_file() || Path::new(&down).is_file() {
+        return vec![];
+    }
+
+    let ups = parts(&up);
+    let downs = parts(&down);
+    if downs.is_empty() {
+        return vec![];
+    }
+
+    let down_of = |up: &String| format!("{}{DOWN_PART_SUFFIX}", up.trim_end_matches(".cql"));
+    let up_of = |down: &String| format!("{}.cql", down.trim_end_matches(DOWN_PART_SUFFIX));
+    let mut unmatched: Vec<String> = ups.iter().filter(|up| !downs.contains(&down_of(up))).cloned().collect();
+    unmatched.extend(downs.iter().filter(|down| !ups.contains(&up_of(down))).cloned());
+    unmatched.iter().map(|part| part.rsplit_once('/').map_or(part.clone(), |(_, name)| name.to_string())).collect()
+}
+
+/// Contents of the up.cql or down.cql at `path`: the file itself, or else its parts one after the other.
+pub(crate) fn migration_file(path: &str) -> Result<String> {
+    match parts(path).as_slice() {
+        [] => file_contents(path),
+        [single] => part_contents(single, path),
+        parts => Ok(parts.iter().map(|part| part_contents(part, path)).collect::<Result<Vec<_>>>()?.join("\n;\n")),
+    }
+}
+
+/// Contents of `part`, one of the `parts` of the up.cql or down.cql at `path`: for a single file migration, the
+/// section of `path`, else the whole file.
+pub(crate) fn part_contents(part: &str, path: &str) -> Result<String> {
+    let contents = file_contents(part)?;
+    let single_file = path.rsplit_once('/').is_some_and(|(dir, _)| part == format!("{dir}.cql"));
+    if !single_file {
+        return Ok(contents);
+    }
+
+    let (up, down) = sections(&contents);
+    Ok(if path.ends_with("/down.cql") { down.unwrap_or_default() } else { up })
+}
+
+/// Up and down statements of a single file migration: what follows `-- migrate:up`, and what follows
+/// `-- migrate:down`, `None` without it. Lines before the first marker, e.g. frontmatter, go with the up statements.
+pub(crate) fn sections(contents: &str) -> (String, Option<String>) {
+    let mut up = String::new();
+    let mut down: Option<String> = None;
+    let mut in_down = false;
+    for line in contents.split_inclusive('\n') {
+        match line.trim() {
+            UP_MARKER => in_down = false,
+            DOWN_MARKER => {
+                in_down = true;
+                down.get_or_insert_with(String::new);
+            }
+            _ if in_down => down.get_or_insert_with(String::new).push_str(line),
+            _ => up.push_str(line),
+        }
+    }
+
+    (up, down)
+}
+
+/// Whether the up.cql or down.cql at `path`, or one of its parts, holds something other than whitespace.
+pub(crate) fn has_migration_content(path: &str) -> bool {
+    parts(path).iter().any(|part| part_contents(part, path).is_ok_and(|contents| !contents.trim().is_empty()))
+}
+
+pub(crate) fn file_contents(path: &str) -> Result<String> {
+    Ok(read_to_string(path)?)
+}
+
+/// Replaces the `${NAME}` placeholders of a migration file with their value in `vars`, so one set of migrations can
+/// target differently named keyspaces per environment. Fails on a placeholder without a value. `$` not followed by
+/// `{` and a name is left as is.
+pub(crate) fn substitute(contents: &str, vars: &BTreeMap<String, String>) -> Result<String> {
+    let mut out = String::with_capacity(contents.len());
+    let mut rest = contents;
+
+    while let Some(at) = rest.find("${") {
+        let after = &rest[at + 2..];
+        let name = after.split_once('}').map(|(name, _)| name).filter(|name| {
+            name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        });
+        let Some(name) = name else {
+            out.push_str(&rest[..at + 2]);
+            rest = after;
+            continue;
+        };
+
+        let value = vars.get(name).ok_or_else(|| {
+            anyhow::anyhow!("No value for ${{{name}}}. Set it with --var {name}=<value> or {VAR_PREFIX}{name}")
+        })?;
+        out.push_str(&rest[..at]);
+        out.push_str(value);
+        rest = &after[name.len() + 1..];
+    }
+    out.push_str(rest);
+
+    Ok(out)
+}
+
+/// `-- key: value` comment lines at the top of a migration file, before the first statement.
+pub(crate) fn frontmatter(contents: &str) -> Vec<(String, String)> {
+    contents
+        .lines()
+        .map(str::trim)
+        .take_while(|line| line.is_empty() || line.starts_with("--"))
+        .filter_map(|line| {
+            let (key, value) = line.trim_start_matches('-').split_once(':')?;
+            Some((key.trim().to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Settings of one migration file overriding the global ones, declared by `-- migrate:` lines at its top.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Directives {
+    /// `timeout=<duration>`: bounds the statements of the file together, instead of the migration timeout.
+    pub(crate) timeout: Option<Duration>,
+    /// `consistency=<level>`: consistency of the statements of the file.
+    pub(crate) consistency: Option<Consistency>,
+    /// `no-split`: executes each file as a single statement, for the odd one splitting at its semicolons breaks.
+    pub(crate) no_split: bool,
+}
+
+/// Directives of a migration file: its `-- migrate:` lines before the first statement. Fails on an unknown one.
+pub(crate) fn directives(contents: &str) -> Result<Directives> {
+    let mut directives = Directives::default();
+    let lines = contents.lines().map(str::trim).take_while(|line| line.is_empty() || line.starts_with("--"));
+    for line in lines {
+        let Some(directive) = line.strip_prefix(DIRECTIVE_PREFIX).map(str::trim) else {
+            continue;
+        };
+
+        let error = |e: anyhow::Error| anyhow::anyhow!("Invalid directive [{line}]: {e}");
+        match directive.split_once('=').map(|(key, value)| (key.trim(), value.trim())) {
+            Some(("timeout", value)) => directives.timeout = crate::db::parse_duration(value).map_err(error)?,
+            Some(("consistency", value)) => directives.consistency = crate::db::parse_consistency(value).map_err(error)?,
+            None if directive == "no-split" => directives.no_split = true,
+            None if directive == "up" || directive == "down" => {}
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Unknown directive [{line}]. Use timeout=<duration>, consistency=<level> or no-split"
+                ))
+            }
+        }
+    }
+
+    Ok(directives)
+}
+
+/// Directives of the up.cql or down.cql at `path`, declared at the top of the file or of its first part.
+pub(crate) fn migration_directives(path: &str) -> Result<Directives> {
+    directives(&migration_file(path)?)
+}
+
+/// Statements of a migration file, split at its semicolons, or the whole file as one under `no_split`.
+pub(crate) fn statements(contents: &str, no_split: bool) -> Vec<&str> {
+    if !no_split {
+        return crate::cql::split(contents);
+    }
+
+    let statement = contents.trim().trim_end_matches(';').trim_end();
+    if crate::cql::strip_comments(statement).is_empty() {
+        vec![]
+    } else {
+        vec![statement]
+    }
+}
+
+/// Statements of the up.cql or down.cql at `path`, across its parts, before the placeholders are replaced.
+pub(crate) fn migration_statements(path: &str) -> Result<Vec<String>> {
+    let no_split = migration_directives(path)?.no_split;
+    let mut statements_of_parts = vec![];
+    for part in parts(path) {
+        let contents = part_contents(&part, path)?;
+        statements_of_parts.extend(statements(&contents, no_split).into_iter().map(str::to_string));
+    }
+
+    Ok(statements_of_parts)
+}
+
+/// Whether the migration's up.cql declares `no_down: true`.
+pub(crate) fn is_irreversible(dir_path: &str, migration: &str) -> bool {
+    migration_file(&file_path(dir_path, migration, "up.cql"))
+        .is_ok_and(|up| frontmatter(&up).iter().any(|(key, value)| key == NO_DOWN && value == "true"))
+}
+
+/// Hex SHA-256 of the up.cql of `migration`, or of its parts, `None` when it can't be read.
+pub(crate) fn checksum(dir_path: &str, migration: &str) -> Option<String> {
+    let up = migration_file(&file_path(dir_path, migration, "up.cql")).ok()?;
+    Some(sha256_hex(up.as_bytes()))
+}
+
+/// Hex SHA-256 of `statements`, as split from a file, to tell whether the statements a failed run completed
+/// are still the first ones of the file.
+pub(crate) fn statements_checksum(statements: &[&str]) -> String {
+    sha256_hex(statements.join("\n;\n").as_bytes())
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    openssl::sha::sha256(bytes).iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// What `migration` does: the `description` frontmatter of its up.cql, or else its name with the underscores
+/// as spaces, e.g. `add users table` for `2024-01-01-000000_add_users_table`.
+pub(crate) fn description(dir_path: &str, migration: &str) -> Option<String> {
+    let declared = migration_file(&file_path(dir_path, migration, "up.cql"))
+        .ok()
+        .and_then(|up| frontmatter(&up).into_iter().find(|(key, _)| key == DESCRIPTION).map(|(_, value)| value));
+
+    declared
+        .or_else(|| Some(migration.split_once('_')?.1.replace('_', " ")))
+        .filter(|description| !description.is_empty())
+}
+
+/// Migrations the up.cql of `migration` declares it squashes.
+pub(crate) fn squashed(dir_path: &str, migration: &str) -> Vec<String> {
+    let Ok(up) = migration_file(&file_path(dir_path, migration, "up.cql")) else {
+        return vec![];
+    };
+
+    frontmatter(&up)
+        .into_iter()
+        .filter(|(key, _)| key == SQUASHES)
+        .flat_map(|(_, value)| value.split(',').map(|m| m.trim().to_string()).filter(|m| !m.is_empty()).collect::<Vec<_>>())
+        .collect()
+}
+
+/// Environments with a directory under `seeds/`, sorted.
+pub(crate) fn seed_envs(dir_path: &str) -> Result<Vec<String>> {
+    let path = format!("{dir_path}/{SEEDS_DIR}");
+    if !Path::new(&path).is_dir() {
+        return Ok(vec![]);
+    }
+
+    let mut envs: Vec<String> = read_dir(path)?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if !path.is_dir() {
+                return None;
+            }
+            Some(path.file_name()?.to_str()?.to_string())
+        })
+        .collect();
+
+    envs.sort();
+    Ok(envs)
+}
+
+/// Seed fixtures of `env`: the `.cql` files of `seeds/<env>/`, sorted, which is also the apply order.
+/// An environment without a seeds directory has no seeds.
+pub(crate) fn seeds(dir_path: &str, env: &str) -> Result<Vec<String>> {
+    let path = format!("{dir_path}/{SEEDS_DIR}/{env}");
+    if !Path::new(&path).is_dir() {
+        return Ok(vec![]);
+    }
+
+    let mut seeds: Vec<String> = read_dir(path)?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if !path.is_file() || path.extension()? != "cql" {
+                return None;
+            }
+            Some(path.file_name()?.to_str()?.to_string())
+        })
+        .collect();
+
+    seeds.sort();
+    Ok(seeds)
+}
+
+pub(crate) fn seed_path(dir_path: &str, env: &str, seed: &str) -> String {
+    format!("{dir_path}/{SEEDS_DIR}/{env}/{seed}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory under the temp dir, removed again when dropped.
+    struct TempDir(String);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let path = std::env::temp_dir().join(format!("scylladb-migrate-{name}-{}", std::process::id()));
+            _ = std::fs::remove_dir_all(&path);
+            create_dir(&path).unwrap();
+            TempDir(path.to_str().unwrap().to_string())
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn vars(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn substitute_placeholders() {
+        let vars = vars(&[("KEYSPACE", "app_prod"), ("RF", "3")]);
+        assert_eq!(
+            substitute("CREATE TABLE ${KEYSPACE}.users (id int PRIMARY KEY); -- rf ${RF}${RF}", &vars).unwrap(),
+            "CREATE TABLE app_prod.users (id int PRIMARY KEY); -- rf 33"
+        );
+    }
+
+    #[test]
+    fn substitute_missing_variable() {
+        let error = substitute("CREATE TABLE ${KEYSPACE}.users (id int PRIMARY KEY);", &vars(&[])).unwrap_err();
+        assert!(error.to_string().contains("No value for ${KEYSPACE}"));
+    }
+
+    #[test]
+    fn substitute_leaves_other_dollars() {
+        let vars = vars(&[("A", "x")]);
+        assert_eq!(substitute("costs $5, a $$ body $$", &vars).unwrap(), "costs $5, a $$ body $$");
+        assert_eq!(substitute("unclosed ${A and ${A}", &vars).unwrap(), "unclosed ${A and x");
+        assert_eq!(substitute("${} ${1A} ${A-B} $", &vars).unwrap(), "${} ${1A} ${A-B} $");
+        assert_eq!(substitute("ends with ${", &vars).unwrap(), "ends with ${");
+    }
+
+    #[test]
+    fn sections_split_at_markers() {
+        let (up, down) = sections("-- migrate:up\nCREATE TABLE a (id int PRIMARY KEY);\n-- migrate:down\nDROP TABLE a;\n");
+        assert_eq!(up, "CREATE TABLE a (id int PRIMARY KEY);\n");
+        assert_eq!(down.as_deref(), Some("DROP TABLE a;\n"));
+    }
+
+    #[test]
+    fn sections_text_before_first_marker_goes_up() {
+        let (up, down) = sections("-- description: users\n-- migrate:up\nCREATE TABLE a (id int PRIMARY KEY);\n");
+        assert_eq!(up, "-- description: users\nCREATE TABLE a (id int PRIMARY KEY);\n");
+        assert_eq!(down, None);
+    }
+
+    #[test]
+    fn sections_down_without_up_marker() {
+        let (up, down) = sections("CREATE TABLE a (id int PRIMARY KEY);\n  -- migrate:down  \nDROP TABLE a;");
+        assert_eq!(up, "CREATE TABLE a (id int PRIMARY KEY);\n");
+        assert_eq!(down.as_deref(), Some("DROP TABLE a;"));
+    }
+
+    #[test]
+    fn sections_empty_down() {
+        let (_, down) = sections("-- migrate:up\nCREATE TABLE a (id int PRIMARY KEY);\n-- migrate:down\n");
+        assert_eq!(down.as_deref(), Some(""));
+    }
+
+    #[test]
+    fn directives_at_the_top() {
+        let directives = directives("-- description: big\n-- migrate:timeout=2m\n-- migrate: consistency = all\n-- migrate:no-split\nSELECT 1;").unwrap();
+        assert_eq!(directives.timeout, Some(Duration::from_secs(120)));
+        assert_eq!(directives.consistency, Some(Consistency::All));
+        assert!(directives.no_split);
+    }
+
+    #[test]
+    fn directives_stop_at_the_first_statement() {
+        let directives = directives("SELECT 1;\n-- migrate:no-split\n-- migrate:bogus\n").unwrap();
+        assert!(!directives.no_split);
+    }
+
+    #[test]
+    fn directives_skip_section_markers() {
+        assert!(!directives("-- migrate:up\n-- migrate:down\n").unwrap().no_split);
+    }
+
+    #[test]
+    fn unknown_directive() {
+        let error = directives("-- migrate:retries=3\nSELECT 1;").unwrap_err();
+        assert!(error.to_string().contains("Unknown directive [-- migrate:retries=3]"));
+        assert!(directives("-- migrate:nosplit\n").is_err());
+    }
+
+    #[test]
+    fn invalid_directive_value() {
+        assert!(directives("-- migrate:timeout=soon\n").unwrap_err().to_string().contains("Invalid directive"));
+        assert!(directives("-- migrate:consistency=most\n").unwrap_err().to_string().contains("Invalid directive"));
+    }
+
+    #[test]
+    fn root_cql_files_taken_for_migrations() {
+        let dir = TempDir::new("subdirectories");
+        write(format!("{}/2024-01-01-000000_users.cql", dir.0), "CREATE TABLE users (id int PRIMARY KEY);").unwrap();
+        write(format!("{}/orders.cql", dir.0), "-- migrate:up\nCREATE TABLE orders (id int PRIMARY KEY);\n").unwrap();
+        write(format!("{}/scratch.cql", dir.0), "SELECT * FROM users;").unwrap();
+        create_dir(format!("{}/2024-01-02-000000_items", dir.0)).unwrap();
+
+        assert_eq!(subdirectories(&dir.0).unwrap(), vec!["2024-01-01-000000_users", "2024-01-02-000000_items", "orders"]);
+    }
+}
@@ -0,0 +1,1869 @@
+use crate::cql;
+use crate::db::{self, ConnectOptions, MetadataRefresh};
+use crate::anonymize::Function;
+use crate::bulk::{self, BulkOptions, Executor};
+use crate::cdc::{self, CdcCheck};
+use crate::conflict::{self, ConflictResolver, Resolution};
+use crate::cql::{Name, ObjectKind};
+use crate::drift::{self, Drift};
+use crate::keyspace;
+use crate::plan::{self, Plan};
+use crate::stats::{self, TableStats};
+use crate::status::{self, MigrationStatus};
+use crate::error::ErrorContext;
+use crate::generate::{self, RowCounts};
+use crate::inventory::{self, InventoryItem};
+use crate::local::{self, subdirectories};
+use crate::progress::{Observer, ProgressTracker};
+use crate::rollback::{self, DownFile, Outcome, Reversibility, RollbackPlan, RoundTrip};
+use crate::scan::{self, ScanOptions};
+use crate::snapshot::{self, Downgrade, Snapshot};
+use crate::validate::{self, Problem};
+use anyhow::{Context, Result};
+use chrono::Utc;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use scylla::frame::response::result::{CqlValue, Row};
+use scylla::query::Query;
+use scylla::statement::{Consistency, PagingState};
+use scylla::Session;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+use tokio_util::sync::CancellationToken;
+
+/// Returned when a run is stopped through its [`CancellationToken`].
+///
+/// The run stops between statements. A migration interrupted half way is recorded as
+/// failed, migrations after it are left untouched.
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl Display for Cancelled {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Migration run cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// A migration file as it would be executed, for dry runs.
+#[derive(Debug)]
+pub struct PlannedMigration {
+    pub migration: String,
+    pub file: String,
+    pub statements: Vec<String>,
+    /// Declared `no_down`: nothing would execute, and only with force.
+    pub irreversible: bool,
+}
+
+impl Display for PlannedMigration {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.file)?;
+        if self.irreversible {
+            return write!(f, "\n    (declared no_down, refused without force)");
+        }
+        if self.statements.is_empty() {
+            return write!(f, "\n    (no statements)");
+        }
+
+        for (i, statement) in self.statements.iter().enumerate() {
+            write!(f, "\n    #{} {statement}", i + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Which applied migrations `down` reverts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Revert {
+    /// The last applied migration.
+    Last,
+    /// The last `n` applied migrations.
+    Steps(usize),
+    /// Every migration applied after the target, which stays applied.
+    To(String),
+    /// Every migration applied by the `up` run whose id starts with this, the same run id `history` shows.
+    /// Fails when migrations of a later run are still applied.
+    Run(String),
+    /// Every applied migration.
+    All,
+}
+
+/// Tracker fix made by `repair`, without executing any migration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Repair {
+    /// Removes the failed rows left behind by failed runs.
+    DeleteFailed,
+    /// Marks a local migration applied.
+    MarkApplied(String),
+    /// Marks a local migration failed.
+    MarkFailed(String),
+    /// Removes the failed rows and the rows of migrations gone locally, so `up` finds no orphans.
+    Reconcile,
+}
+
+/// Applies and reverts the migrations found in a directory.
+pub struct Migrator {
+    options: ConnectOptions,
+    dir_path: String,
+    session: Session,
+    observer: Option<Arc<dyn Observer>>,
+    resolver: Option<Arc<dyn ConflictResolver>>,
+    force: bool,
+    cdc_check: CdcCheck,
+    keyspaces: Vec<String>,
+    ignore_drift: bool,
+    /// Held while migrations are applied or reverted, renewed alongside every statement.
+    lock: Option<db::Lock>,
+    resume: bool,
+}
+
+impl Migrator {
+    pub async fn new(options: ConnectOptions, dir_path: impl Into<String>) -> Result<Self> {
+        let session = db::session(&options)
+            .await
+            .with_context(|| error_context(&options))?;
+
+        Ok(Migrator { options, dir_path: dir_path.into(), session, observer: None, resolver: None, force: false, cdc_check: CdcCheck::Warn, keyspaces: vec![], ignore_drift: false, lock: None, resume: false })
+    }
+
+    /// Reports the progress of every run to `observer`.
+    pub fn with_observer(mut self, observer: Arc<dyn Observer>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Lets `resolver` decide about conflicts found by `up`, instead of failing the run.
+    pub fn with_resolver(mut self, resolver: Arc<dyn ConflictResolver>) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    /// Lets reverts cross migrations declared `no_down`, marking their tracker row rolled back without executing anything.
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// What `up` does about migrations altering or dropping tables with CDC enabled. Warns by default.
+    pub fn with_cdc_check(mut self, cdc_check: CdcCheck) -> Self {
+        self.cdc_check = cdc_check;
+        self
+    }
+
+    /// Keyspaces the migrations are meant for. `up` refuses statements on any other keyspace.
+    pub fn with_keyspaces(mut self, keyspaces: Vec<String>) -> Self {
+        self.keyspaces = keyspaces;
+        self
+    }
+
+    /// Lets `up` proceed when the up.cql of an applied migration changed since it ran, with a warning.
+    pub fn with_ignore_drift(mut self, ignore_drift: bool) -> Self {
+        self.ignore_drift = ignore_drift;
+        self
+    }
+
+    /// Lets `up` skip the statements a failed run of a migration completed, instead of running the whole up.cql again.
+    /// Refused when those statements changed since.
+    pub fn with_resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+
+    pub fn options(&self) -> &ConnectOptions {
+        &self.options
+    }
+
+    /// Applies every pending migration, in order. Returns the applied migrations.
+    pub async fn up(&mut self, cancel: &CancellationToken) -> Result<Vec<String>> {
+        self.up_steps(None, cancel).await
+    }
+
+    /// Applies the next `steps` pending migrations, in order, or every one without a limit.
+    /// Returns the applied migrations.
+    pub async fn up_steps(&mut self, steps: Option<usize>, cancel: &CancellationToken) -> Result<Vec<String>> {
+        self.up_until(steps, None, cancel).await
+    }
+
+    /// Reverts the migrations applied after `target` and applies the pending ones up to and including it,
+    /// so exactly the migrations up to `target` are applied. Returns the reverted and the applied migrations.
+    pub async fn goto(&mut self, target: &str, cancel: &CancellationToken) -> Result<(Vec<String>, Vec<String>)> {
+        let (target, newer) = self.goto_reverts(target).await?;
+        let reverted = self.revert(&newer, cancel).await?;
+        let applied = self.up_until(None, Some(&target), cancel).await?;
+
+        Ok((reverted, applied))
+    }
+
+    /// The down files `goto` would execute, in execution order.
+    pub async fn goto_dry_run(&self, target: &str) -> Result<Vec<PlannedMigration>> {
+        let (_, newer) = self.goto_reverts(target).await?;
+        self.planned_downs(&newer)
+    }
+
+    /// Resolved `goto` target and the applied migrations newer than it, oldest first.
+    async fn goto_reverts(&self, target: &str) -> Result<(String, Vec<String>)> {
+        let applied = self.applied().await?;
+        let target = match local::find(&self.dir_path, target) {
+            Ok(target) => target,
+            Err(_) if applied.iter().any(|m| m == target) => target.to_string(),
+            Err(e) => return Err(e).with_context(|| self.context()),
+        };
+
+        let newer = applied.into_iter().filter(|m| *m > target).collect();
+        Ok((target, newer))
+    }
+
+    /// Applies `steps` pending migrations at most, and none after `target`.
+    async fn up_until(&mut self, steps: Option<usize>, target: Option<&str>, cancel: &CancellationToken) -> Result<Vec<String>> {
+        let dir_path = self.dir_path.clone();
+        let local_migrations = subdirectories(&dir_path)
+            .with_context(|| self.context().file(&dir_path))?;
+        let db_migrations = self.applied_with_squashes(&local_migrations).await?;
+        log::info!("local migrations: {local_migrations:?}, applied migrations: {db_migrations:?}");
+        self.check_checksums().await?;
+
+        let mut migrations_to_apply: Vec<String> = local_migrations
+            .iter()
+            .filter(
+                |entry| !db_migrations.contains(entry)
+            )
+            .cloned()
+            .collect();
+
+        // serialize is not implemented for local, so using utc
+        let now = Utc::now();
+
+        self.resolve_conflicts(&local_migrations, &db_migrations, &mut migrations_to_apply, now).await?;
+        if let Some(target) = target {
+            migrations_to_apply.retain(|migration| migration.as_str() <= target);
+        }
+        if let Some(steps) = steps {
+            migrations_to_apply.truncate(steps);
+        }
+        log::info!("migrations to apply: {migrations_to_apply:?}");
+        self.check_keyspaces(&migrations_to_apply).await?;
+        self.check_cdc(&migrations_to_apply).await?;
+        self.apply(&migrations_to_apply, db_migrations, now, cancel).await
+    }
+
+    /// Reverts the last applied migration and applies it again, the loop of iterating on a migration.
+    /// Returns the migration.
+    pub async fn redo(&mut self, cancel: &CancellationToken) -> Result<Vec<String>> {
+        let reverted = self.down(&Revert::Last, cancel).await?;
+        let db_migrations = self.history()
+            .await
+            .with_context(|| self.context())?;
+
+        self.apply(&reverted, db_migrations, Utc::now(), cancel).await
+    }
+
+    /// Applies `migrations` in order, recording each in the tracker. `db_migrations` are the applied ones,
+    /// which are skipped. Holds the migration lock meanwhile.
+    async fn apply(
+        &mut self,
+        migrations: &[String],
+        db_migrations: Vec<String>,
+        now: chrono::DateTime<Utc>,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<String>> {
+        if migrations.is_empty() {
+            return self.apply_locked(migrations, db_migrations, now, cancel).await;
+        }
+
+        self.take_lock().await?;
+        // another run may have applied some of them before this one got the lock
+        let result = match self.history().await.with_context(|| self.context()) {
+            Ok(db_migrations) => self.apply_locked(migrations, db_migrations, now, cancel).await,
+            Err(e) => Err(e),
+        };
+        self.release_lock(result).await
+    }
+
+    async fn apply_locked(
+        &mut self,
+        migrations: &[String],
+        mut db_migrations: Vec<String>,
+        now: chrono::DateTime<Utc>,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<String>> {
+        let dir_path = self.dir_path.clone();
+        let mut refresh = MetadataRefresh::new(self.options.refresh_interval);
+        let mut progress = self.tracker(migrations, "up.cql").await;
+        let mut applied = vec![];
+        let run_id = db::new_run_id()?;
+        if !migrations.is_empty() {
+            log::info!("run id: {run_id}");
+        }
+        // failed runs that completed some statements, which --resume skips
+        let mut partial: Vec<db::MigrationData> = vec![];
+        if !migrations.is_empty() {
+            partial = self.tracker_rows().await?;
+            partial.retain(|row| row.is_up() && row.status == "failed" && row.statements_done.is_some_and(|done| done > 0));
+        }
+
+        for migration in migrations {
+            if cancel.is_cancelled() {
+                return Err(Cancelled.into());
+            }
+
+            if db::ensure_connected(&mut self.session, &self.options)
+                .await
+                .with_context(|| self.context().migration(migration))?
+            {
+                // the connection dropped, possibly after the previous migration was recorded
+                db_migrations = self.history()
+                    .await
+                    .with_context(|| self.context().migration(migration))?;
+            }
+
+            if db_migrations.contains(migration) {
+                log::info!("migration already applied, skipping: {migration}");
+                continue;
+            }
+
+            let up = format!("{dir_path}/{migration}/up.cql");
+            let skip = match partial.iter().find(|row| row.id == *migration) {
+                Some(row) if self.resume => self.resume_point(migration, &up, row)?,
+                Some(row) => {
+                    let done = row.statements_done.unwrap_or_default();
+                    log::info!("{migration} completed {done} statement(s) before failing last time, resume with up --resume to skip them");
+                    0
+                }
+                None => 0,
+            };
+
+            progress.start_migration(migration);
+            let started = Instant::now();
+            let resp = self.apply_migration_from(&mut refresh, &mut progress, migration, up.as_str(), skip, cancel).await;
+            let run = db::Run {
+                success: resp.is_ok(),
+                duration: Some(started.elapsed()),
+                run_id: Some(run_id),
+                failure: resp.as_ref().err().map(|e| failure(e, &up)),
+                skipped: skip,
+                ..self.recorded(migration, now)
+            };
+            db::upsert(&self.session, migration.clone(), run)
+                .await
+                .with_context(|| self.context().migration(migration))?;
+
+            resp?;
+            progress.migration_done();
+            applied.push(migration.clone());
+        }
+
+        Ok(applied)
+    }
+
+    /// Applies each pending migration, reverts it and compares the schema before and after,
+    /// then applies it again so the next one builds on it. Meant for scratch clusters.
+    /// Stops at the first migration that fails to apply or revert. Holds the migration lock meanwhile.
+    pub async fn test_reversibility(&mut self, cancel: &CancellationToken) -> Result<Vec<RoundTrip>> {
+        self.take_lock().await?;
+        let result = self.test_reversibility_locked(cancel).await;
+        self.release_lock(result).await
+    }
+
+    async fn test_reversibility_locked(&self, cancel: &CancellationToken) -> Result<Vec<RoundTrip>> {
+        let dir_path = self.dir_path.clone();
+        let applied = self.applied().await?;
+        let pending: Vec<String> = subdirectories(&dir_path)
+            .with_context(|| self.context().file(&dir_path))?
+            .into_iter()
+            .filter(|migration| !applied.contains(migration))
+            .collect();
+
+        let mut refresh = MetadataRefresh::new(self.options.refresh_interval);
+        let mut report = vec![];
+
+        for migration in &pending {
+            if cancel.is_cancelled() {
+                return Err(Cancelled.into());
+            }
+
+            let up = local::file_path(&dir_path, migration, "up.cql");
+            let down = local::file_path(&dir_path, migration, "down.cql");
+            let irreversible = local::is_irreversible(&dir_path, migration);
+
+            let before = self.describe(None).await?;
+            let outcome = match self.round_trip(&mut refresh, migration, &up, &down, irreversible, cancel).await {
+                Ok(()) if irreversible => {
+                    Outcome::Skipped(format!("declared {}, applied without testing", local::NO_DOWN))
+                }
+                Ok(()) => rollback::compare(&before, &self.describe(None).await?),
+                Err(e) if e.is::<Cancelled>() => return Err(e),
+                Err(e) => {
+                    report.push(RoundTrip { migration: migration.clone(), outcome: Outcome::Error(format!("{e:#}")) });
+                    break;
+                }
+            };
+
+            // leave the migration applied, so the next one runs against the schema it expects
+            if !irreversible {
+                let mut progress = self.tracker(std::slice::from_ref(migration), "up.cql").await;
+                progress.start_migration(migration);
+                if let Err(e) = self.apply_migration(&mut refresh, &mut progress, migration, &up, cancel).await {
+                    report.push(RoundTrip { migration: migration.clone(), outcome: Outcome::Error(format!("{e:#}")) });
+                    break;
+                }
+                progress.migration_done();
+            }
+            db::upsert(&self.session, migration.clone(), self.recorded(migration, Utc::now()))
+                .await
+                .with_context(|| self.context().migration(migration))?;
+
+            report.push(RoundTrip { migration: migration.clone(), outcome });
+        }
+
+        Ok(report)
+    }
+
+    /// Applies `up` then `down`. Irreversible migrations are only applied.
+    async fn round_trip(
+        &self,
+        refresh: &mut MetadataRefresh,
+        migration: &str,
+        up: &str,
+        down: &str,
+        irreversible: bool,
+        cancel: &CancellationToken,
+    ) -> Result<()> {
+        let mut progress = self.tracker(&[migration.to_string()], "up.cql").await;
+        progress.start_migration(migration);
+        self.apply_migration(refresh, &mut progress, migration, up, cancel).await?;
+        progress.migration_done();
+
+        if irreversible {
+            return Ok(());
+        }
+
+        let mut progress = self.tracker(&[migration.to_string()], "down.cql").await;
+        progress.start_migration(migration);
+        self.apply_migration(refresh, &mut progress, migration, down, cancel).await?;
+        progress.migration_done();
+
+        Ok(())
+    }
+
+    /// Applies the pending seed fixtures of `env`, in order. Seeds are tracked apart from the
+    /// migrations, in a history of their own per environment. Returns the applied seeds.
+    pub async fn seed(&mut self, env: &str, cancel: &CancellationToken) -> Result<Vec<String>> {
+        if env.is_empty() || env.contains(['/', '.']) {
+            return Err(anyhow::anyhow!("Invalid seed environment: [{env}]"));
+        }
+
+        let partition = match db::partition() {
+            db::DEFAULT_PARTITION => format!("seed_{env}"),
+            migrations => format!("{migrations}_seed_{env}"),
+        };
+        let seeds = local::seeds(&self.dir_path, env).with_context(|| self.context())?;
+        let seeded = db::list_in(&self.session, &partition, self.options.history_consistency)
+            .await
+            .with_context(|| self.context())?;
+        let pending: Vec<String> = seeds.into_iter().filter(|seed| !seeded.contains(seed)).collect();
+        log::info!("seeds to apply for [{env}]: {pending:?}");
+
+        let paths: Vec<String> = pending.iter().map(|seed| local::seed_path(&self.dir_path, env, seed)).collect();
+        let mut refresh = MetadataRefresh::new(self.options.refresh_interval);
+        let statements_total = paths
+            .iter()
+            .filter_map(|path| local::migration_statements(path).ok())
+            .map(|statements| statements.len())
+            .sum();
+        let mut progress = ProgressTracker::new(self.observer.clone(), pending.len(), statements_total);
+        let mut applied = vec![];
+
+        for (seed, path) in pending.iter().zip(&paths) {
+            if cancel.is_cancelled() {
+                return Err(Cancelled.into());
+            }
+            db::ensure_connected(&mut self.session, &self.options)
+                .await
+                .with_context(|| self.context().migration(seed))?;
+
+            progress.start_migration(seed);
+            let started = Instant::now();
+            let resp = self.apply_migration(&mut refresh, &mut progress, seed, path, cancel).await;
+            let run = db::Run {
+                success: resp.is_ok(),
+                run_at: Utc::now(),
+                duration: Some(started.elapsed()),
+                failure: resp.as_ref().err().map(|e| failure(e, path)),
+                ..Default::default()
+            };
+            db::upsert_in(&self.session, &partition, seed.clone(), run)
+                .await
+                .with_context(|| self.context().migration(seed))?;
+
+            resp?;
+            progress.migration_done();
+            applied.push(seed.clone());
+        }
+
+        Ok(applied)
+    }
+
+    /// Fills the application tables with `counts` rows of generated data. Materialized views are
+    /// left to the cluster, counter tables are skipped. Returns the tables and the rows inserted,
+    /// `None` for skipped tables.
+    pub async fn generate_seed(
+        &mut self,
+        counts: &RowCounts,
+        seed: u64,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<(Name, Option<usize>)>> {
+        let tables: Vec<Name> = db::schema_objects(&self.session)
+            .await
+            .with_context(|| self.context())?
+            .into_iter()
+            .filter(|(kind, _)| *kind == ObjectKind::Table)
+            .map(|(_, name)| name)
+            .collect();
+        let columns = db::columns(&self.session).await.with_context(|| self.context())?;
+        let mut random = generate::Random::new(seed);
+        let mut report = vec![];
+
+        for table in tables {
+            let count = counts.get(&table);
+            if count == 0 {
+                continue;
+            }
+
+            let table_columns: Vec<&db::Column> = columns.iter().filter(|c| c.table == table).collect();
+            let Some(inserts) = generate::inserts(&table, &table_columns, count, &mut random) else {
+                report.push((table, None));
+                continue;
+            };
+
+            db::ensure_connected(&mut self.session, &self.options)
+                .await
+                .with_context(|| self.context())?;
+            for (index, insert) in inserts.iter().enumerate() {
+                let context = || self.context().file(&table.to_string()).statement(index + 1);
+                tokio::select! {
+                    biased;
+                    _ = cancel.cancelled() => return Err(anyhow::Error::new(Cancelled).context(context())),
+                    resp = self.session.query_unpaged(insert.as_str(), &[]) => { resp.with_context(context)?; }
+                }
+            }
+            log::info!("generated {count} row(s) in {table}");
+            report.push((table, Some(count)));
+        }
+
+        Ok(report)
+    }
+
+    /// Rewrites the `rules` columns of every row of `table` through their anonymization function,
+    /// scanning the table like `backfill` does and writing through the throttled executor.
+    /// Returns the number of rows rewritten.
+    pub async fn anonymize(
+        &self,
+        table: &Name,
+        rules: &[(String, Function)],
+        scan: &ScanOptions,
+        bulk: &BulkOptions,
+        checkpoint: Option<&str>,
+        cancel: &CancellationToken,
+    ) -> Result<usize> {
+        let context = || self.context().file(&table.to_string());
+        let columns = self.table_columns(table).await?;
+        let key: Vec<String> = columns
+            .iter()
+            .filter(|c| c.kind == "partition_key" || c.kind == "clustering")
+            .map(|c| cql::quote(&c.name))
+            .collect();
+        for (column, _) in rules {
+            match columns.iter().find(|c| c.name == *column) {
+                None => return Err(anyhow::anyhow!("Column [{column}] not found in [{table}]")),
+                Some(c) if c.kind == "partition_key" || c.kind == "clustering" => return Err(anyhow::anyhow!("Key column [{column}] can't be rewritten")),
+                Some(c) if !["text", "varchar", "ascii"].contains(&c.cql_type.as_str()) => {
+                    return Err(anyhow::anyhow!("Column [{column}] is {}, only text columns can be anonymized", c.cql_type));
+                }
+                Some(_) => {}
+            }
+        }
+
+        let targets: Vec<String> = rules.iter().map(|(column, _)| cql::quote(column)).collect();
+        let set: Vec<String> = targets.iter().map(|c| format!("{c} = ?")).collect();
+        let condition: Vec<String> = key.iter().map(|c| format!("{c} = ?")).collect();
+        let update = self
+            .session
+            .prepare(format!("UPDATE {} SET {} WHERE {}", table.quoted(), set.join(", "), condition.join(" AND ")))
+            .await
+            .with_context(context)?;
+
+        let executor = Executor::new(&self.session, bulk.clone());
+        let selected: Vec<String> = key.iter().cloned().chain(targets).collect();
+        let rewrite = async |rows: Vec<Row>| {
+            let rows = rows.into_iter().map(|row| {
+                let (key_values, values) = row.columns.split_at(key.len());
+                let mut bound: Vec<Option<CqlValue>> = values
+                    .iter()
+                    .zip(rules)
+                    .map(|(value, (column, function))| match value {
+                        Some(CqlValue::Text(text)) => Some(CqlValue::Text(function.apply(column, text))),
+                        Some(CqlValue::Ascii(text)) => Some(CqlValue::Ascii(function.apply(column, text))),
+                        other => other.clone(),
+                    })
+                    .collect();
+                bound.extend(key_values.iter().cloned());
+                bound
+            });
+            executor.run(&update, rows, cancel).await
+        };
+
+        let name = checkpoint.map_or_else(|| scan::scan_name("anonymize", table, ""), str::to_string);
+        self.locked(self.scan_table(table, &columns, &selected, scan, &name, cancel, rewrite)).await
+    }
+
+    /// Reads `columns` of every row of `table` and executes `statement` once per row, with the values
+    /// of `columns` bound in order, through the throttled executor. The table is scanned token range
+    /// by token range, `scan.parallelism` ranges at a time. Completed ranges are kept in the migrate
+    /// keyspace under `name`, defaulting to one derived from the table and the statement, so an
+    /// interrupted backfill resumes where it stopped. Holds the migration lock. Returns the rows processed.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn backfill(
+        &self,
+        table: &Name,
+        columns: &[String],
+        statement: &str,
+        scan: &ScanOptions,
+        bulk: &BulkOptions,
+        checkpoint: Option<&str>,
+        cancel: &CancellationToken,
+    ) -> Result<usize> {
+        let context = || self.context().file(statement);
+        let table_columns = self.table_columns(table).await?;
+        for column in columns {
+            if !table_columns.iter().any(|c| c.name == *column) {
+                return Err(anyhow::anyhow!("Column [{column}] not found in [{table}]"));
+            }
+        }
+
+        let prepared = self.session.prepare(statement).await.with_context(context)?;
+        let executor = Executor::new(&self.session, bulk.clone());
+        let selected: Vec<String> = columns.iter().map(|c| cql::quote(c)).collect();
+        let write = async |rows: Vec<Row>| executor.run(&prepared, rows.into_iter().map(|row| row.columns), cancel).await;
+
+        let name = checkpoint.map_or_else(|| scan::scan_name("backfill", table, statement), str::to_string);
+        self.locked(self.scan_table(table, &table_columns, &selected, scan, &name, cancel, write)).await
+    }
+
+    async fn table_columns(&self, table: &Name) -> Result<Vec<db::Column>> {
+        let columns: Vec<db::Column> = db::columns(&self.session)
+            .await
+            .with_context(|| self.context())?
+            .into_iter()
+            .filter(|c| c.table == *table)
+            .collect();
+        if columns.is_empty() {
+            return Err(anyhow::anyhow!("Table [{table}] not found")).with_context(|| self.context());
+        }
+
+        Ok(columns)
+    }
+
+    /// Pages through `selected` of every row of `table`, range by range, and hands each page to
+    /// `handler`, which returns the number of rows it processed. Run under the migration lock. Completed ranges are kept under
+    /// `name` in the migrate keyspace, so an interrupted scan resumes with the remaining ones.
+    #[allow(clippy::too_many_arguments)]
+    async fn scan_table(
+        &self,
+        table: &Name,
+        columns: &[db::Column],
+        selected: &[String],
+        scan: &ScanOptions,
+        name: &str,
+        cancel: &CancellationToken,
+        handler: impl AsyncFn(Vec<Row>) -> Result<usize>,
+    ) -> Result<usize> {
+        let context = || self.context().file(&table.to_string());
+        let partition_key: Vec<String> = columns
+            .iter()
+            .filter(|c| c.kind == "partition_key")
+            .map(|c| cql::quote(&c.name))
+            .collect();
+        let token = format!("token({})", partition_key.join(", "));
+        let mut select = self
+            .session
+            .prepare(format!("SELECT {} FROM {} WHERE {token} >= ? AND {token} <= ?", selected.join(", "), table.quoted()))
+            .await
+            .with_context(context)?;
+        select.set_page_size(1000);
+
+        let done = db::scan_ranges_done(&self.session, name, scan.ranges)
+            .await
+            .with_context(context)?;
+        let pending: Vec<(usize, (i64, i64))> = scan::token_ranges(scan.ranges)
+            .into_iter()
+            .enumerate()
+            .filter(|(range, _)| !done.contains(range))
+            .collect();
+        log::info!("scanning {table} as [{name}]: {} of {} token ranges already done", done.len(), scan.ranges);
+
+        let finished = AtomicUsize::new(done.len());
+        let (select, handler, finished) = (&select, &handler, &finished);
+        let rows = stream::iter(pending)
+            .map(|(range, (start, end))| async move {
+                let mut rows = 0;
+                let mut paging_state = PagingState::start();
+                loop {
+                    if cancel.is_cancelled() {
+                        return Err(anyhow::Error::new(Cancelled).context(context()));
+                    }
+
+                    let (result, paging) = self
+                        .session
+                        .execute_single_page(select, (start, end), paging_state)
+                        .await
+                        .with_context(context)?;
+                    rows += handler(result.rows.unwrap_or_default()).await.with_context(context)?;
+
+                    match paging.into_paging_control_flow() {
+                        ControlFlow::Continue(next) => paging_state = next,
+                        ControlFlow::Break(()) => break,
+                    }
+                }
+
+                db::complete_scan_range(&self.session, name, scan.ranges, range, rows)
+                    .await
+                    .with_context(context)?;
+                let done = finished.fetch_add(1, Ordering::SeqCst) + 1;
+                log::info!("token range {range} done: {rows} row(s), {done}/{} ranges", scan.ranges);
+                Ok(rows)
+            })
+            .buffer_unordered(scan.parallelism.max(1))
+            .try_fold(0, |total, rows| async move { Ok(total + rows) })
+            .await?;
+
+        // a finished scan starts over next time
+        db::clear_scan(&self.session, name, scan.ranges).await.with_context(context)?;
+        Ok(rows)
+    }
+
+    /// Executes `statement` once per key, through the throttled executor, holding the migration lock. Each key is a
+    /// line of comma separated values, one per bind marker. Returns the number of statements executed.
+    pub async fn bulk(
+        &self,
+        statement: &str,
+        keys: impl IntoIterator<Item = String>,
+        bulk: &BulkOptions,
+        cancel: &CancellationToken,
+    ) -> Result<usize> {
+        let context = || self.context().file(statement);
+        let prepared = self.session.prepare(statement).await.with_context(context)?;
+        let rows = keys
+            .into_iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| bulk::parse_values(&prepared, &line))
+            .collect::<Result<Vec<_>>>()
+            .with_context(context)?;
+
+        let executor = Executor::new(&self.session, bulk.clone());
+        self.locked(async { executor.run(&prepared, rows, cancel).await.with_context(context) }).await
+    }
+
+    /// Fails on pending statements referencing a keyspace outside the targets, or one that neither exists
+    /// nor is created earlier in the run, before anything is applied.
+    async fn check_keyspaces(&self, migrations: &[String]) -> Result<()> {
+        if migrations.is_empty() {
+            return Ok(());
+        }
+
+        let existing = db::keyspaces(&self.session).await.with_context(|| self.context())?;
+        let pending: Vec<(String, Vec<cql::Statement>)> = migrations
+            .iter()
+            .filter_map(|migration| {
+                let up = self.contents(&local::file_path(&self.dir_path, migration, "up.cql")).ok()?;
+                Some((migration.clone(), cql::parse_all(&up)))
+            })
+            .collect();
+
+        let references = keyspace::check(&pending, &self.keyspaces, Some(&existing));
+        if references.is_empty() {
+            return Ok(());
+        }
+
+        let references: Vec<String> = references.iter().map(ToString::to_string).collect();
+        Err(anyhow::anyhow!("Migrations reference unexpected keyspaces:\n{}", references.join("\n"))).with_context(|| self.context())
+    }
+
+    /// Warns about, or fails on, pending statements altering or dropping tables with CDC enabled.
+    async fn check_cdc(&self, migrations: &[String]) -> Result<()> {
+        if self.cdc_check == CdcCheck::Off || migrations.is_empty() {
+            return Ok(());
+        }
+
+        let cdc_tables = db::cdc_tables(&self.session).await.with_context(|| self.context())?;
+        if cdc_tables.is_empty() {
+            return Ok(());
+        }
+
+        let warnings: Vec<String> = migrations
+            .iter()
+            .filter_map(|migration| {
+                let up = self.contents(&local::file_path(&self.dir_path, migration, "up.cql")).ok()?;
+                Some(cdc::check(migration, &up, &cdc_tables))
+            })
+            .flatten()
+            .map(|warning| warning.to_string())
+            .collect();
+        if warnings.is_empty() {
+            return Ok(());
+        }
+
+        if self.cdc_check == CdcCheck::Fail {
+            return Err(anyhow::anyhow!("Migrations touch tables with CDC enabled:\n{}", warnings.join("\n")))
+                .with_context(|| self.context());
+        }
+        for warning in warnings {
+            log::warn!("warning: {warning}");
+        }
+
+        Ok(())
+    }
+
+    /// Fails on out of order and orphaned migrations, unless the resolver settles each of them.
+    async fn resolve_conflicts(
+        &self,
+        local_migrations: &[String],
+        db_migrations: &[String],
+        migrations_to_apply: &mut Vec<String>,
+        now: chrono::DateTime<Utc>,
+    ) -> Result<()> {
+        let conflicts = conflict::detect(local_migrations, db_migrations);
+        if conflicts.is_empty() {
+            return Ok(());
+        }
+
+        let Some(resolver) = &self.resolver else {
+            let report: Vec<String> = conflicts.iter().map(|c| c.to_string()).collect();
+            return Err(anyhow::anyhow!("Conflicts found, resolve them interactively or repair the tracker:\n{}", report.join("\n")))
+                .with_context(|| self.context());
+        };
+
+        for conflict in &conflicts {
+            let migration = conflict.migration().to_string();
+            let resolution = resolver.resolve(conflict);
+            if !conflict.options().contains(&resolution) {
+                return Err(anyhow::anyhow!("[{resolution}] does not resolve: {conflict}"));
+            }
+
+            match resolution {
+                Resolution::Apply => {}
+                Resolution::Skip => migrations_to_apply.retain(|m| *m != migration),
+                Resolution::MarkApplied => {
+                    db::upsert(&self.session, migration.clone(), self.recorded(&migration, now))
+                        .await
+                        .with_context(|| self.context().migration(&migration))?;
+                    migrations_to_apply.retain(|m| *m != migration);
+                }
+                Resolution::Forget => {
+                    db::delete(&self.session, migration.clone())
+                        .await
+                        .with_context(|| self.context().migration(&migration))?;
+                }
+                Resolution::Abort => return Err(anyhow::anyhow!("Aborted on conflict: {conflict}")),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the local migrations up to and including `up_to`, all applied, with a single migration made of
+    /// their up.cql files, and their down.cql files in reverse order, declaring what it squashes. Rewrites the
+    /// tracker rows here, other environments adopt it on their next `up`. Returns it and the squashed migrations.
+    pub async fn squash(&self, up_to: &str) -> Result<(String, Vec<String>)> {
+        let local_migrations = subdirectories(&self.dir_path)
+            .with_context(|| self.context().file(&self.dir_path))?;
+        let target = local::find(&self.dir_path, up_to).with_context(|| self.context())?;
+        let applied = self.applied_with_squashes(&local_migrations).await?;
+
+        let squashed: Vec<String> = local_migrations.into_iter().filter(|m| *m <= target).collect();
+        let pending: Vec<&String> = squashed.iter().filter(|m| !applied.contains(m)).collect();
+        if !pending.is_empty() {
+            return Err(anyhow::anyhow!("Only applied migrations can be squashed, pending: {pending:?}")).with_context(|| self.context());
+        }
+        if squashed.len() < 2 {
+            return Err(anyhow::anyhow!("Nothing to squash up to [{target}]"));
+        }
+
+        let irreversible = squashed.iter().any(|m| local::is_irreversible(&self.dir_path, m));
+        let missing: Vec<&String> = squashed
+            .iter()
+            .filter(|m| rollback::down_file(&self.dir_path, m) == DownFile::Missing)
+            .collect();
+        if !irreversible && !missing.is_empty() {
+            return Err(anyhow::anyhow!("Migrations without a down.cql: {missing:?}")).with_context(|| self.context());
+        }
+
+        let concat = |migrations: Vec<&String>, file: &str| -> Result<String> {
+            let mut out = String::new();
+            for migration in migrations {
+                let path = local::file_path(&self.dir_path, migration, file);
+                let source = local::migration_file(&path).with_context(|| self.context().migration(migration).file(&path))?;
+                let source: Vec<&str> = source.lines().filter(|line| !line.starts_with(&format!("-- {}:", local::SQUASHES))).collect();
+                out.push_str(&format!("\n-- squashed from {migration}\n{}\n", source.join("\n").trim()));
+            }
+            Ok(out)
+        };
+
+        let mut up = format!("-- {}: {}\n", local::SQUASHES, squashed.join(","));
+        if irreversible {
+            up.push_str(&format!("-- {}: true\n", local::NO_DOWN));
+        }
+        up.push_str(&concat(squashed.iter().collect(), "up.cql")?);
+        let down = if irreversible { None } else { Some(concat(squashed.iter().rev().collect(), "down.cql")?) };
+
+        let timestamp = target.split_once('_').map_or(target.as_str(), |(timestamp, _)| timestamp);
+        let migration = format!("{timestamp}_squashed");
+        local::write_migration(&self.dir_path, &migration, &up, down.as_deref())
+            .with_context(|| self.context().migration(&migration))?;
+        self.adopt_squash(&migration, &squashed).await?;
+        for squashed in &squashed {
+            let path = format!("{}/{squashed}", self.dir_path);
+            let removed = if Path::new(&path).is_dir() {
+                std::fs::remove_dir_all(&path)
+            } else {
+                std::fs::remove_file(format!("{path}.cql"))
+            };
+            removed
+                .map_err(anyhow::Error::from)
+                .with_context(|| self.context().file(&path))?;
+        }
+
+        Ok((migration, squashed))
+    }
+
+    /// Applied migrations, counting a squashed migration applied where everything it squashes is.
+    /// Its tracker rows are rewritten then, unless read only.
+    async fn applied_with_squashes(&self, local_migrations: &[String]) -> Result<Vec<String>> {
+        let mut applied = self.history()
+            .await
+            .with_context(|| self.context())?;
+
+        for migration in local_migrations {
+            let squashed = local::squashed(&self.dir_path, migration);
+            let done = squashed.iter().filter(|m| applied.contains(m)).count();
+            if applied.contains(migration) || done == 0 {
+                continue;
+            }
+            if done < squashed.len() {
+                return Err(anyhow::anyhow!(
+                    "[{migration}] squashes {squashed:?}, only partly applied here. Apply them with a release that still has them first"
+                ))
+                .with_context(|| self.context());
+            }
+
+            if !self.options.read_only {
+                log::info!("adopting squashed migration {migration}, replacing {squashed:?}");
+                self.adopt_squash(migration, &squashed).await?;
+            }
+            applied.retain(|m| !squashed.contains(m));
+            applied.push(migration.clone());
+            applied.sort();
+        }
+
+        Ok(applied)
+    }
+
+    async fn adopt_squash(&self, migration: &str, squashed: &[String]) -> Result<()> {
+        db::upsert(&self.session, migration.to_string(), self.recorded(migration, Utc::now()))
+            .await
+            .with_context(|| self.context().migration(migration))?;
+        for squashed in squashed {
+            db::delete(&self.session, squashed.clone())
+                .await
+                .with_context(|| self.context().migration(squashed))?;
+        }
+
+        Ok(())
+    }
+
+    /// Every tracker row of the migrations, failed ones included, at the configured history consistency.
+    async fn tracker_rows(&self) -> Result<Vec<db::MigrationData>> {
+        let read = async {
+            if self.options.read_only && !db::tracker_exists(&self.session).await? {
+                return Ok(vec![]);
+            }
+            if self.options.read_only {
+                let missing = db::missing_tracker_columns(&self.session).await?;
+                if !missing.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "The tracker was created by an older version and lacks {missing:?}. Run a command that isn't read only, e.g. `status`, to upgrade it"
+                    ));
+                }
+            }
+            db::rows_in(&self.session, db::partition(), self.options.history_consistency).await
+        };
+
+        read.await.with_context(|| self.context())
+    }
+
+    /// Fails when the up.cql of an applied migration changed since it ran, unless drift is ignored.
+    async fn check_checksums(&self) -> Result<()> {
+        let modified = validate::modified(&self.dir_path, &self.tracker_rows().await?);
+        if modified.is_empty() {
+            return Ok(());
+        }
+
+        if self.ignore_drift {
+            log::warn!("warning: up.cql changed since it was applied, ignored: {modified:?}");
+            return Ok(());
+        }
+        Err(anyhow::anyhow!(
+            "up.cql changed since it was applied: {modified:?}. Restore the files, or ignore the drift to proceed anyway"
+        ))
+        .with_context(|| self.context())
+    }
+
+    /// Tracker read of the applied migrations, at the configured history consistency.
+    async fn history(&self) -> anyhow::Result<Vec<String>> {
+        if self.options.read_only && !db::tracker_exists(&self.session).await? {
+            return Ok(vec![]);
+        }
+        db::list(&self.session, self.options.history_consistency).await
+    }
+
+    /// Every local migration with its state in the tracker: pending, applied or failed, and when it ran.
+    /// Tracked migrations missing locally are included.
+    pub async fn status(&self) -> Result<Vec<MigrationStatus>> {
+        let local_migrations = subdirectories(&self.dir_path)
+            .with_context(|| self.context().file(&self.dir_path))?;
+        let rows = self.tracker_rows().await?;
+
+        Ok(status::statuses(&local_migrations, rows))
+    }
+
+    /// The tracker rows, applied, failed and rolled back, in the order they ran.
+    pub async fn tracked(&self) -> Result<Vec<MigrationStatus>> {
+        let local_migrations = subdirectories(&self.dir_path)
+            .with_context(|| self.context().file(&self.dir_path))?;
+        let rows = self.tracker_rows().await?;
+
+        Ok(status::history(&local_migrations, rows))
+    }
+
+    /// Applied migrations gone locally, malformed migration names and failed tracker rows.
+    pub async fn validate(&self) -> Result<Vec<Problem>> {
+        let local_migrations = subdirectories(&self.dir_path)
+            .with_context(|| self.context().file(&self.dir_path))?;
+        let rows = self.tracker_rows().await?;
+
+        Ok(validate::validate(&self.dir_path, &local_migrations, &rows))
+    }
+
+    /// Migrations whose tracker row `repair` would write or remove.
+    pub async fn repair_plan(&self, repair: &Repair) -> Result<Vec<String>> {
+        if let Repair::MarkApplied(id) | Repair::MarkFailed(id) = repair {
+            let migration = local::find(&self.dir_path, id).with_context(|| self.context())?;
+            return Ok(vec![migration]);
+        }
+
+        let local_migrations = subdirectories(&self.dir_path)
+            .with_context(|| self.context().file(&self.dir_path))?;
+        let rows = self.tracker_rows().await?;
+
+        Ok(rows
+            .into_iter()
+            .filter(|row| row.status == "failed" || (*repair == Repair::Reconcile && !local_migrations.contains(&row.id)))
+            .map(|row| row.id)
+            .collect())
+    }
+
+    /// Applies `repair` to the tracker rows of `migrations`, as planned by `repair_plan`.
+    pub async fn repair(&self, repair: &Repair, migrations: &[String]) -> Result<()> {
+        for migration in migrations {
+            let result = match repair {
+                Repair::MarkApplied(_) => db::upsert(&self.session, migration.clone(), self.recorded(migration, Utc::now())).await,
+                Repair::MarkFailed(_) => {
+                    let run = db::Run { success: false, checksum: None, ..self.recorded(migration, Utc::now()) };
+                    db::upsert(&self.session, migration.clone(), run).await
+                }
+                Repair::DeleteFailed | Repair::Reconcile => db::delete(&self.session, migration.clone()).await,
+            };
+            result.with_context(|| self.context().migration(migration))?;
+        }
+
+        Ok(())
+    }
+
+    /// Records the local migrations up to and including `up_to`, or every one, as applied without executing them,
+    /// for adopting the tool on an existing keyspace. Returns the newly recorded migrations.
+    pub async fn baseline(&self, up_to: Option<&str>) -> Result<Vec<String>> {
+        let local_migrations = subdirectories(&self.dir_path)
+            .with_context(|| self.context().file(&self.dir_path))?;
+        let up_to = match up_to {
+            Some(id) => Some(local::find(&self.dir_path, id).with_context(|| self.context())?),
+            None => None,
+        };
+        let db_migrations = self.history()
+            .await
+            .with_context(|| self.context())?;
+
+        let now = Utc::now();
+        let mut recorded = vec![];
+        for migration in local_migrations {
+            if up_to.as_ref().is_some_and(|up_to| migration > *up_to) || db_migrations.contains(&migration) {
+                continue;
+            }
+
+            db::upsert(&self.session, migration.clone(), self.recorded(&migration, now))
+                .await
+                .with_context(|| self.context().migration(&migration))?;
+            recorded.push(migration);
+        }
+
+        Ok(recorded)
+    }
+
+    /// Successfully applied migrations, oldest first.
+    pub async fn applied(&self) -> Result<Vec<String>> {
+        self.history()
+            .await
+            .with_context(|| self.context())
+    }
+
+    /// Reverts the applied migrations selected by `range`, newest first. Returns the reverted migrations.
+    pub async fn down(&mut self, range: &Revert, cancel: &CancellationToken) -> Result<Vec<String>> {
+        let migrations_to_revert = self.migrations_to_revert(range).await?;
+        self.revert(&migrations_to_revert, cancel).await
+    }
+
+    /// What `up_steps` would execute, in execution order, without touching the database.
+    /// Each applied migration would get a tracker row. Conflicts fail the dry run, as they can't be resolved in it.
+    pub async fn up_dry_run(&self, steps: Option<usize>) -> Result<Vec<PlannedMigration>> {
+        let local_migrations = subdirectories(&self.dir_path)
+            .with_context(|| self.context().file(&self.dir_path))?;
+        let db_migrations = self.applied_with_squashes(&local_migrations).await?;
+        self.check_checksums().await?;
+
+        let conflicts = conflict::detect(&local_migrations, &db_migrations);
+        if !conflicts.is_empty() {
+            let report: Vec<String> = conflicts.iter().map(|c| c.to_string()).collect();
+            return Err(anyhow::anyhow!("Conflicts found, up would stop on them:\n{}", report.join("\n")))
+                .with_context(|| self.context());
+        }
+
+        local_migrations
+            .iter()
+            .filter(|migration| !db_migrations.contains(migration))
+            .take(steps.unwrap_or(usize::MAX))
+            .map(|migration| self.planned(migration, "up.cql"))
+            .collect()
+    }
+
+    /// The pending migrations `up_steps` would apply and their statements, without touching the database.
+    pub async fn plan(&self, steps: Option<usize>) -> Result<Plan> {
+        Ok(plan::plan(self.up_dry_run(steps).await?))
+    }
+
+    /// What `down` would execute, in execution order, without touching the database.
+    /// Each reverted migration's tracker row would be marked rolled back.
+    pub async fn down_dry_run(&self, range: &Revert) -> Result<Vec<PlannedMigration>> {
+        let migrations = self.migrations_to_revert(range).await?;
+        self.planned_downs(&migrations)
+    }
+
+    /// Down files of `migrations`, given oldest first, in execution order.
+    fn planned_downs(&self, migrations: &[String]) -> Result<Vec<PlannedMigration>> {
+        self.check_down_files(migrations)?;
+        migrations
+            .iter()
+            .rev()
+            .map(|migration| {
+                if local::is_irreversible(&self.dir_path, migration) {
+                    return Ok(PlannedMigration {
+                        migration: migration.clone(),
+                        file: local::file_path(&self.dir_path, migration, "down.cql"),
+                        statements: vec![],
+                        irreversible: true,
+                    });
+                }
+                self.planned(migration, "down.cql")
+            })
+            .collect()
+    }
+
+    fn check_down_files(&self, migrations: &[String]) -> Result<()> {
+        let missing: Vec<&String> = migrations
+            .iter()
+            .filter(|migration| rollback::down_file(&self.dir_path, migration) == DownFile::Missing)
+            .collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        Err(anyhow::anyhow!(
+            "Migrations without a down.cql: {missing:?}. Add one, or declare `-- {}: true` in up.cql and revert with force",
+            local::NO_DOWN
+        ))
+        .with_context(|| self.context())
+    }
+
+    /// Applied migrations selected by `range`, oldest first.
+    async fn migrations_to_revert(&self, range: &Revert) -> Result<Vec<String>> {
+        let mut db_migrations = self.applied().await?;
+        let steps = match range {
+            Revert::All => return Ok(db_migrations),
+            Revert::To(target) => {
+                let (_, range) = self.rollback_range(Some(target)).await?;
+                return Ok(range.into_iter().rev().collect());
+            }
+            Revert::Run(run_id) => return self.run_migrations(run_id, &db_migrations).await,
+            Revert::Last => 1,
+            Revert::Steps(steps) => *steps,
+        };
+
+        let keep = db_migrations.len().saturating_sub(steps);
+        Ok(db_migrations.split_off(keep))
+    }
+
+    /// Applied migrations of the run `run_id`, oldest first. `applied` are all the applied migrations.
+    async fn run_migrations(&self, run_id: &str, applied: &[String]) -> Result<Vec<String>> {
+        let rows = self.tracker_rows().await?;
+        let runs: Vec<Uuid> = rows.iter().filter_map(|row| row.run_id).filter(|id| id.to_string().starts_with(run_id)).collect();
+        let Some(&run) = runs.first() else {
+            return Err(anyhow::anyhow!("No run matching [{run_id}] in the history")).with_context(|| self.context());
+        };
+        if runs.iter().any(|id| *id != run) {
+            return Err(anyhow::anyhow!("Run id [{run_id}] is ambiguous, give more of it")).with_context(|| self.context());
+        }
+
+        let migrations: Vec<String> = rows
+            .into_iter()
+            .filter(|row| row.run_id == Some(run) && applied.contains(&row.id))
+            .map(|row| row.id)
+            .collect();
+        let later: Vec<&String> = match migrations.first() {
+            Some(oldest) => applied.iter().filter(|m| *m > oldest && !migrations.contains(m)).collect(),
+            None => vec![],
+        };
+        if !later.is_empty() {
+            return Err(anyhow::anyhow!("Migrations applied after run [{run}] are still applied: {later:?}. Revert them first"))
+                .with_context(|| self.context());
+        }
+
+        Ok(migrations)
+    }
+
+    fn planned(&self, migration: &str, file: &str) -> Result<PlannedMigration> {
+        let file = local::file_path(&self.dir_path, migration, file);
+        let context = || self.context().migration(migration).file(&file);
+        let no_split = local::migration_directives(&file).with_context(context)?.no_split;
+        let mut statements = vec![];
+        for part in parts(&file) {
+            let contents = self.part_contents(&part, &file).with_context(|| self.context().migration(migration).file(&part))?;
+            statements.extend(local::statements(&contents, no_split).into_iter().map(cql::strip_comments));
+        }
+
+        Ok(PlannedMigration { migration: migration.to_string(), file, statements, irreversible: false })
+    }
+
+    /// Removes the migration lock, whoever holds it, after the run holding it was killed. Returns the holder, if any.
+    pub async fn unlock(&self) -> Result<Option<String>> {
+        db::unlock(&self.session).await.with_context(|| self.context())
+    }
+
+    /// Removes the whole migration history, failed runs included. Meant for after `down(&Revert::All, ..)`.
+    pub async fn clear_history(&self) -> Result<()> {
+        db::clear(&self.session)
+            .await
+            .with_context(|| self.context())
+    }
+
+    /// Drops `keyspaces` with everything in them, for rebuilding from scratch. The tracker keyspace is refused,
+    /// clear the history instead.
+    pub async fn drop_keyspaces(&self, keyspaces: &[String]) -> Result<()> {
+        if keyspaces.iter().any(|keyspace| keyspace == db::keyspace()) {
+            return Err(anyhow::anyhow!("Refusing to drop the tracker keyspace [{}]", db::keyspace()));
+        }
+
+        for keyspace in keyspaces {
+            log::info!("dropping keyspace: {keyspace}");
+            self.session
+                .query_unpaged(format!("DROP KEYSPACE IF EXISTS {}", cql::quote(keyspace)), &[])
+                .await
+                .with_context(|| self.context())?;
+        }
+
+        Ok(())
+    }
+
+    /// Plans reverting every applied migration newer than `target`, newest first.
+    /// `target` itself stays applied.
+    pub async fn rollback_plan(&self, target: &str) -> Result<RollbackPlan> {
+        let (target, range) = self.rollback_range(Some(target)).await?;
+        let steps = range
+            .into_iter()
+            .map(|migration| {
+                let down = rollback::down_file(&self.dir_path, &migration);
+                (migration, down)
+            })
+            .collect();
+
+        Ok(RollbackPlan { target: target.unwrap_or_default(), steps })
+    }
+
+    /// Checks that every applied migration newer than `target`, or every applied one, can be reverted.
+    pub async fn rollback_check(&self, target: Option<&str>) -> Result<Vec<Reversibility>> {
+        let (_, range) = self.rollback_range(target).await?;
+        Ok(range
+            .into_iter()
+            .map(|migration| {
+                let status = rollback::check(&self.dir_path, &migration);
+                Reversibility { migration, status }
+            })
+            .collect())
+    }
+
+    /// Resolved target and the applied migrations newer than it, newest first.
+    async fn rollback_range(&self, target: Option<&str>) -> Result<(Option<String>, Vec<String>)> {
+        let applied = self.applied().await?;
+        let Some(target) = target else {
+            return Ok((None, applied.into_iter().rev().collect()));
+        };
+
+        let target = local::find(&self.dir_path, target)
+            .ok()
+            .filter(|t| applied.contains(t))
+            .or_else(|| applied.iter().find(|m| m.starts_with(target)).cloned())
+            .ok_or_else(|| anyhow::anyhow!("[{target}] is not an applied migration"))?;
+
+        let range = applied.into_iter().filter(|m| *m > target).rev().collect();
+        Ok((Some(target), range))
+    }
+
+    /// Current schema and applied migrations, to roll back to with `downgrade` later.
+    pub async fn snapshot(&self) -> Result<Snapshot> {
+        let applied = self.applied().await?;
+        let schema = self
+            .describe(None)
+            .await?
+            .iter()
+            .map(|statement| format!("{};\n", statement.trim().trim_end_matches(';')))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        Ok(Snapshot { applied, schema })
+    }
+
+    /// Schema level downgrade from the live schema back to `snapshot`.
+    pub async fn downgrade_plan(&self, snapshot: &Snapshot) -> Result<Downgrade> {
+        let applied = self.applied().await?;
+        let live = self.snapshot().await?;
+        Ok(snapshot::downgrade(snapshot, &live.schema, &applied))
+    }
+
+    /// Executes the statements of `downgrade` and removes the tracker rows it lists.
+    pub async fn downgrade(&mut self, downgrade: &Downgrade, cancel: &CancellationToken) -> Result<()> {
+        let mut refresh = MetadataRefresh::new(self.options.refresh_interval);
+
+        for (index, statement) in downgrade.statements.iter().enumerate() {
+            let context = self.context().file("snapshot downgrade").statement(index + 1);
+            let context = || context.clone();
+            db::ensure_connected(&mut self.session, &self.options).await.with_context(context)?;
+            refresh.tick(&self.session).await.with_context(context)?;
+
+            tokio::select! {
+                biased;
+                _ = cancel.cancelled() => return Err(anyhow::Error::new(Cancelled).context(context())),
+                resp = self.session.query_unpaged(statement.as_str(), &[]) => { resp.with_context(context)?; }
+            }
+            log::info!("executed #{}: {statement}", index + 1);
+        }
+
+        for migration in &downgrade.forget {
+            db::delete(&self.session, migration.clone())
+                .await
+                .with_context(|| self.context().migration(migration))?;
+        }
+
+        Ok(())
+    }
+
+    /// Reverts `migrations`, given oldest first, in reverse order. Returns the reverted migrations.
+    /// A migration whose down fails is recorded failed, with the statement that failed.
+    pub async fn revert(&mut self, migrations_to_revert: &[String], cancel: &CancellationToken) -> Result<Vec<String>> {
+        let dir_path = self.dir_path.clone();
+
+        if migrations_to_revert.is_empty() {
+            log::info!("no migrations to revert");
+            return Ok(vec![]);
+        }
+
+        let irreversible: Vec<&String> = migrations_to_revert
+            .iter()
+            .filter(|migration| local::is_irreversible(&dir_path, migration))
+            .collect();
+        if !irreversible.is_empty() && !self.force {
+            return Err(anyhow::anyhow!("Migrations declared no_down, revert them with force: {irreversible:?}"))
+                .with_context(|| self.context());
+        }
+
+        // checked upfront, so a missing file doesn't stop the run with half of the migrations reverted
+        self.check_down_files(migrations_to_revert)?;
+
+        log::info!("applied migrations to revert: [{:?}]", migrations_to_revert);
+
+        self.take_lock().await?;
+        // another run may have reverted some of them before this one got the lock
+        let result = match self.history().await.with_context(|| self.context()) {
+            Ok(applied) => {
+                let migrations: Vec<String> = migrations_to_revert.iter().filter(|m| applied.contains(m)).cloned().collect();
+                self.revert_locked(&migrations, cancel).await
+            }
+            Err(e) => Err(e),
+        };
+        self.release_lock(result).await
+    }
+
+    async fn revert_locked(&mut self, migrations_to_revert: &[String], cancel: &CancellationToken) -> Result<Vec<String>> {
+        let dir_path = self.dir_path.clone();
+
+        let mut refresh = MetadataRefresh::new(self.options.refresh_interval);
+        let mut progress = self.tracker(migrations_to_revert, "down.cql").await;
+        let mut reverted = vec![];
+
+        for migration in migrations_to_revert.iter().rev() {
+            if cancel.is_cancelled() {
+                return Err(Cancelled.into());
+            }
+
+            let reconnected = db::ensure_connected(&mut self.session, &self.options)
+                .await
+                .with_context(|| self.context().migration(migration))?;
+            if reconnected
+                && !self.history()
+                    .await
+                    .with_context(|| self.context().migration(migration))?
+                    .contains(migration)
+            {
+                log::info!("migration already reverted, skipping: {migration}");
+                continue;
+            }
+
+            let down = format!("{dir_path}/{migration}/down.cql");
+            progress.start_migration(migration);
+            if local::is_irreversible(&dir_path, migration) {
+                log::info!("crossing migration declared no_down, nothing to execute: {migration}");
+            } else {
+                let started = Instant::now();
+                if let Err(e) = self.apply_migration(&mut refresh, &mut progress, migration, down.as_str(), cancel).await {
+                    // partially reverted: no longer applied, and its history tells where the down failed. How far it got
+                    // is left out, counted against down.cql it would be taken for the statements done of an up
+                    let run = db::Run {
+                        success: false,
+                        duration: Some(started.elapsed()),
+                        failure: Some(db::Failure { statements_done: None, statements_done_checksum: None, ..failure(&e, &down) }),
+                        down: true,
+                        ..self.recorded(migration, Utc::now())
+                    };
+                    db::upsert(&self.session, migration.clone(), run)
+                        .await
+                        .with_context(|| self.context().migration(migration))?;
+                    return Err(e);
+                }
+            }
+            db::roll_back(&self.session, migration.clone(), Utc::now())
+                .await
+                .with_context(|| self.context().migration(migration))?;
+            progress.migration_done();
+            reverted.push(migration.clone());
+        }
+
+        Ok(reverted)
+    }
+
+    /// Lists the schema objects of the application keyspaces, annotated with the applied
+    /// migration that last created or altered each of them.
+    pub async fn inventory(&self) -> Result<Vec<InventoryItem>> {
+        let applied = self.history()
+            .await
+            .with_context(|| self.context())?;
+        let objects = db::schema_objects(&self.session)
+            .await
+            .with_context(|| self.context())?;
+
+        Ok(inventory::annotate(&self.dir_path, &applied, objects))
+    }
+
+    /// Size estimates, large partitions and tombstone settings of the tables the pending migrations touch,
+    /// to judge their blast radius before applying them.
+    pub async fn stats(&self) -> Result<Vec<TableStats>> {
+        let local_migrations = subdirectories(&self.dir_path)
+            .with_context(|| self.context().file(&self.dir_path))?;
+        let applied = self.history()
+            .await
+            .with_context(|| self.context())?;
+        let pending: Vec<String> = local_migrations.into_iter().filter(|m| !applied.contains(m)).collect();
+
+        let mut stats = vec![];
+        for (table, migrations) in stats::touched(&self.dir_path, &pending) {
+            let table_stats = db::table_stats(&self.session, &table, migrations)
+                .await
+                .with_context(|| self.context())?;
+            stats.push(table_stats);
+        }
+
+        Ok(stats)
+    }
+
+    /// Current CQL definition of a keyspace, a `keyspace.object`, or the whole schema.
+    pub async fn describe(&self, target: Option<&str>) -> Result<Vec<String>> {
+        db::describe(&self.session, target)
+            .await
+            .with_context(|| self.context())
+    }
+
+    /// Compares the objects the applied migrations define with the live schema.
+    pub async fn drift(&self) -> Result<Vec<Drift>> {
+        let applied = self.history()
+            .await
+            .with_context(|| self.context())?;
+        let objects = db::schema_objects(&self.session)
+            .await
+            .with_context(|| self.context())?;
+
+        Ok(drift::detect(&self.dir_path, &applied, &objects))
+    }
+
+    /// Suggested up.cql and down.cql contents bringing the migrations back in line with `drifts`.
+    /// Returns `None` when there is nothing to suggest.
+    pub async fn remediation(&self, drifts: &[Drift]) -> Result<Option<(String, String)>> {
+        let mut live_definitions = BTreeMap::new();
+        for drift in drifts {
+            if let Drift::Unmanaged { name, .. } = drift {
+                let definition = self.describe(Some(&name.to_string())).await?;
+                if let Some(definition) = definition.into_iter().next() {
+                    live_definitions.insert(name.clone(), definition);
+                }
+            }
+        }
+
+        let (up, down) = drift::remediation(drifts, &live_definitions);
+        if up.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some((up, down)))
+    }
+
+    fn context(&self) -> ErrorContext {
+        error_context(&self.options)
+    }
+
+    /// Counts the statements of `file` in each migration upfront, so progress has a total.
+    async fn tracker(&self, migrations: &[String], file: &str) -> ProgressTracker {
+        let statements = |migration: &str, file: &str| {
+            local::migration_statements(&local::file_path(&self.dir_path, migration, file)).map(|statements| statements.len())
+        };
+        let statements_total = migrations.iter().filter_map(|migration| statements(migration, file).ok()).sum();
+        let tracker = ProgressTracker::new(self.observer.clone(), migrations.len(), statements_total);
+        // only up runs record how long they took
+        if self.observer.is_none() || file != "up.cql" {
+            return tracker;
+        }
+
+        // the recorded runs of up files that ran every statement, failed and resumed ones aside as they ran part of it
+        let (duration_ms, statements_recorded) = self.tracker_rows()
+            .await
+            .unwrap_or_default()
+            .iter()
+            .filter(|row| row.is_up() && row.status != "failed" && row.statements_done.is_none())
+            .filter_map(|row| Some((u64::try_from(row.duration_ms?).ok()?, statements(&row.id, file).ok()?)))
+            .fold((0, 0), |(duration_ms, count), (ms, statements)| (duration_ms + ms, count + statements));
+        tracker.with_history(Duration::from_millis(duration_ms), statements_recorded)
+    }
+
+    async fn apply_migration(
+        &self,
+        refresh: &mut MetadataRefresh,
+        progress: &mut ProgressTracker,
+        migration: &str,
+        migration_path: &str,
+        cancel: &CancellationToken,
+    ) -> Result<()> {
+        self.apply_migration_from(refresh, progress, migration, migration_path, 0, cancel).await
+    }
+
+    /// Like `apply_migration`, skipping the first `skip` statements, completed by a failed run.
+    async fn apply_migration_from(
+        &self,
+        refresh: &mut MetadataRefresh,
+        progress: &mut ProgressTracker,
+        migration: &str,
+        migration_path: &str,
+        skip: usize,
+        cancel: &CancellationToken,
+    ) -> Result<()> {
+        let directives = local::migration_directives(migration_path)
+            .with_context(|| self.context().migration(migration).file(migration_path))?;
+        let timeout = directives.timeout.or(self.options.migration_timeout);
+        let deadline = timeout.map(|timeout| tokio::time::Instant::now() + timeout);
+        // statements of the previous parts, the skipped ones counting across parts
+        let mut before = 0;
+        for part in parts(migration_path) {
+            let context = self.context().migration(migration).file(&part);
+            let query = self.part_contents(&part, migration_path).with_context(|| context.clone())?;
+
+            log::info!("applying migration: {part}");
+
+            let statements = local::statements(&query, directives.no_split);
+            for (index, query) in statements.iter().copied().enumerate() {
+                if before + index < skip {
+                    log::info!("skipping statement #{}, completed by the failed run", index + 1);
+                    progress.statement_done();
+                    continue;
+                }
+
+                let context = || context.clone().statement(index + 1);
+                refresh.tick(&self.session).await.with_context(context)?;
+                if let Some(lock) = &self.lock {
+                    lock.renew(&self.session).await.with_context(context)?;
+                }
+
+                let statement = cql::strip_comments(query);
+                let timed_out = async {
+                    match deadline {
+                        Some(deadline) => tokio::time::sleep_until(deadline).await,
+                        None => std::future::pending().await,
+                    }
+                };
+                tokio::select! {
+                    biased;
+                    _ = cancel.cancelled() => return Err(anyhow::Error::new(Cancelled).context(context())),
+                    _ = timed_out => {
+                        return Err(anyhow::anyhow!("Migration timed out after {:?}", timeout.unwrap_or_default()).context(context()));
+                    }
+                    lost = self.heartbeat() => return Err(lost.context(context())),
+                    resp = self.run_statement(&statement, query, directives.consistency) => { resp.with_context(context)?; }
+                }
+
+                progress.statement_done();
+            }
+            before += statements.len();
+        }
+
+        log::info!("migration applied. Successfully");
+        Ok(())
+    }
+
+    /// The migration file at `path`, its placeholders replaced.
+    fn contents(&self, path: &str) -> Result<String> {
+        local::substitute(&local::migration_file(path)?, &self.options.vars)
+    }
+
+    /// `part` of the migration file at `path`, its placeholders replaced.
+    fn part_contents(&self, part: &str, path: &str) -> Result<String> {
+        local::substitute(&local::part_contents(part, path)?, &self.options.vars)
+    }
+
+    /// How many statements of the up.cql at `path` to skip when resuming `migration`, whose last run failed as `row`
+    /// records. Fails when the statements it completed are no longer the first ones of the file.
+    fn resume_point(&self, migration: &str, path: &str, row: &db::MigrationData) -> Result<usize> {
+        let done = row.statements_done.unwrap_or_default().max(0) as usize;
+        let statements = local::migration_statements(path).with_context(|| self.context().migration(migration).file(path))?;
+        let statements: Vec<&str> = statements.iter().map(String::as_str).collect();
+        let unchanged = statements.len() >= done
+            && row.statements_done_checksum.as_deref() == Some(local::statements_checksum(&statements[..done]).as_str());
+        if !unchanged {
+            return Err(anyhow::anyhow!(
+                "The {done} statement(s) the failed run completed changed since, so it can't be resumed. \
+                Finish or undo it by hand and use force, or run it again in full without resume"
+            ))
+            .with_context(|| self.context().migration(migration).file(path));
+        }
+
+        log::info!("resuming {migration} after the {done} statement(s) its failed run completed");
+        Ok(done)
+    }
+
+    /// Renews the migration lock the run holds for as long as it is awaited. Only returns once the lock is lost,
+    /// never without a lock.
+    async fn heartbeat(&self) -> anyhow::Error {
+        match &self.lock {
+            Some(lock) => lock.heartbeat(&self.session).await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Awaits `work` holding a migration lock of its own, renewed alongside it, so no migration runs meanwhile.
+    /// Fails as soon as the lock is lost, dropping `work`.
+    async fn locked<T>(&self, work: impl std::future::Future<Output = Result<T>>) -> Result<T> {
+        let lock = db::lock(&self.session, self.options.lock_ttl).await.with_context(|| self.context())?;
+        let result = tokio::select! {
+            lost = lock.heartbeat(&self.session) => Err(lost).with_context(|| self.context()),
+            result = work => result,
+        };
+        self.release(lock, result).await
+    }
+
+    /// Takes the migration lock for the run.
+    async fn take_lock(&mut self) -> Result<()> {
+        let lock = db::lock(&self.session, self.options.lock_ttl).await.with_context(|| self.context())?;
+        self.lock = Some(lock);
+        Ok(())
+    }
+
+    /// Releases the migration lock once the run is over, returning its `result`. A release failing after a
+    /// failed run only warns, the lock expiring on its own, so the error of the run is the one reported.
+    async fn release_lock<T>(&mut self, result: Result<T>) -> Result<T> {
+        let Some(lock) = self.lock.take() else {
+            return result;
+        };
+
+        self.release(lock, result).await
+    }
+
+    async fn release<T>(&self, lock: db::Lock, result: Result<T>) -> Result<T> {
+        match (lock.release(&self.session).await, result) {
+            (Ok(()), result) => result,
+            (Err(e), Ok(_)) => Err(e).with_context(|| self.context()),
+            (Err(e), Err(result)) => {
+                log::warn!("warning: unable to release the migration lock, it expires after {:?}: {e}", self.options.lock_ttl);
+                Err(result)
+            }
+        }
+    }
+
+    /// A successful run of `migration`, with the checksum and description of its up.cql, and no duration:
+    /// for migrations recorded without running them.
+    fn recorded(&self, migration: &str, run_at: chrono::DateTime<Utc>) -> db::Run {
+        db::Run {
+            success: true,
+            run_at,
+            checksum: local::checksum(&self.dir_path, migration),
+            duration: None,
+            description: local::description(&self.dir_path, migration),
+            run_id: None,
+            failure: None,
+            down: false,
+            skipped: 0,
+        }
+    }
+
+    /// Waits until every node runs the schema a DDL statement produced, so the next statement, or the next migration,
+    /// doesn't reach a node that doesn't know the new table or column yet.
+    async fn await_schema_agreement(&self) -> Result<()> {
+        match self.session.await_schema_agreement().await {
+            Ok(_) => Ok(()),
+            Err(e) if db::schema_disagreement(&e) => Err(self.schema_disagreement()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn schema_disagreement(&self) -> anyhow::Error {
+        anyhow::anyhow!(
+            "The statement was applied, but the nodes didn't agree on the schema within {:?}. \
+            Check the unreachable nodes with nodetool describecluster, or raise the schema agreement timeout",
+            self.options.schema_agreement_timeout,
+        )
+    }
+
+    /// Executes the migration statement `statement`, stripped of the comments of `query`, then waits until its effects
+    /// can be relied on: the objects it creates active on Amazon Keyspaces, and the nodes agreeing on the schema it
+    /// changes. The deadline and the cancellation of the migration cover all of it.
+    async fn run_statement(&self, statement: &str, query: &str, consistency: Option<Consistency>) -> Result<()> {
+        self.execute(statement, consistency).await?;
+        if self.options.amazon_keyspaces {
+            wait_until_active(&self.session, query).await?;
+        }
+        if plan::is_schema_change(&cql::parse(query)) {
+            self.await_schema_agreement().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Executes the migration statement `query`, at `consistency` instead of the session one when set, retrying
+    /// transient failures as the retry policy allows.
+    async fn execute(&self, query: &str, consistency: Option<Consistency>) -> Result<()> {
+        let policy = &self.options.retry;
+        let mut backoff = policy.backoff;
+        let mut attempt = 0;
+        let mut query = Query::new(query);
+        if let Some(consistency) = consistency {
+            query.set_consistency(consistency);
+        }
+
+        loop {
+            match self.session.query_unpaged(query.clone(), &[]).await {
+                Ok(_) => return Ok(()),
+                Err(e) if db::schema_disagreement(&e) => return Err(self.schema_disagreement()),
+                Err(e) if bulk::pushback(&e) && attempt < policy.max_retries => {
+                    attempt += 1;
+                    log::warn!("statement failed ({e}), retry {attempt}/{} in {}ms", policy.max_retries, backoff.as_millis());
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(bulk::MAX_BACKOFF);
+                }
+                Err(e) if attempt > 0 => return Err(e).context(format!("statement failed after {attempt} retries")),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+/// Waits until the keyspace created, or the table created or altered, by `query` can be used, Amazon Keyspaces
+/// applying DDL asynchronously. Unqualified tables are skipped, their keyspace being unknown here.
+async fn wait_until_active(session: &Session, query: &str) -> Result<()> {
+    let statement = cql::parse(query);
+    let (Some(name), Some(keyspace)) = (&statement.name, statement.keyspace()) else {
+        return Ok(());
+    };
+
+    match (statement.action, statement.kind) {
+        (cql::Action::Create, Some(ObjectKind::Keyspace)) => db::wait_until_active(session, keyspace, None).await,
+        (cql::Action::Create | cql::Action::Alter, Some(ObjectKind::Table)) => {
+            db::wait_until_active(session, keyspace, Some(&name.name)).await
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Why a run of the file at `path` failed with `error`: the driver error, and the failing statement, when the error
+/// names one.
+fn failure(error: &anyhow::Error, path: &str) -> db::Failure {
+    let context = error.downcast_ref::<ErrorContext>();
+    let no_split = local::migration_directives(path).is_ok_and(|directives| directives.no_split);
+    // the index is within the part that failed, the recorded one within the whole file
+    let before: usize = local::parts(path)
+        .iter()
+        .take_while(|part| context.and_then(|context| context.file.as_ref()) != Some(*part))
+        .filter_map(|part| local::part_contents(part, path).ok())
+        .map(|contents| local::statements(&contents, no_split).len())
+        .sum();
+    let statement = context.and_then(|context| context.statement).map(|index| before + index);
+    let statements = local::migration_statements(path).unwrap_or_default();
+    let statements: Vec<&str> = statements.iter().map(String::as_str).collect();
+    let done = statement.map(|index| (index - 1).min(statements.len()));
+
+    db::Failure {
+        message: Some(error.root_cause().to_string()),
+        statement,
+        cql: statement.and_then(|index| statements.get(index - 1)).map(|statement| statement.to_string()),
+        statements_done: done,
+        statements_done_checksum: done.map(|done| local::statements_checksum(&statements[..done])),
+    }
+}
+
+/// The cluster and environment of the run `options` configure.
+fn error_context(options: &ConnectOptions) -> ErrorContext {
+    let context = ErrorContext::new(&options.db_url);
+    match &options.environment {
+        Some(environment) => context.environment(environment),
+        None => context,
+    }
+}
+
+/// The parts of the migration file at `path`, or `path` itself when missing, for its read to report it.
+fn parts(path: &str) -> Vec<String> {
+    Some(local::parts(path)).filter(|parts| !parts.is_empty()).unwrap_or_else(|| vec![path.to_string()])
+}
@@ -0,0 +1,138 @@
+use crate::cql::{self, Action, Risk, Statement};
+use crate::migrator::PlannedMigration;
+use std::fmt::{Display, Formatter};
+
+/// What `up` would run, for approving a deployment before it happens.
+#[derive(Debug)]
+pub struct Plan {
+    /// Pending migrations in apply order.
+    pub migrations: Vec<MigrationPlan>,
+}
+
+#[derive(Debug)]
+pub struct MigrationPlan {
+    pub migration: String,
+    pub file: String,
+    /// In execution order.
+    pub statements: Vec<Statement>,
+}
+
+impl MigrationPlan {
+    pub fn risk(&self) -> Risk {
+        self.statements.iter().map(|s| s.risk()).max().unwrap_or(Risk::Low)
+    }
+}
+
+impl Plan {
+    pub fn statements(&self) -> usize {
+        self.migrations.iter().map(|m| m.statements.len()).sum()
+    }
+
+    pub fn risk(&self) -> Risk {
+        self.migrations.iter().map(|m| m.risk()).max().unwrap_or(Risk::Low)
+    }
+
+    /// The plan as a JSON document, statements numbered in execution order across migrations.
+    pub fn render_json(&self) -> String {
+        let mut order = 0;
+        let migrations: Vec<String> = self.migrations
+            .iter()
+            .map(|migration| {
+                let statements: Vec<String> = migration.statements
+                    .iter()
+                    .map(|statement| {
+                        order += 1;
+                        format!(
+                            "{{\"order\":{order},\"summary\":{},\"risk\":\"{}\",\"schema_change\":{},\"cql\":{}}}",
+                            json_string(&statement.summary()),
+                            statement.risk(),
+                            is_schema_change(statement),
+                            json_string(&statement.text),
+                        )
+                    })
+                    .collect();
+
+                format!(
+                    "{{\"id\":{},\"file\":{},\"risk\":\"{}\",\"statements\":[{}]}}",
+                    json_string(&migration.migration),
+                    json_string(&migration.file),
+                    migration.risk(),
+                    statements.join(","),
+                )
+            })
+            .collect();
+
+        format!(
+            "{{\"migrations\":[{}],\"statements\":{},\"risk\":\"{}\"}}",
+            migrations.join(","),
+            self.statements(),
+            self.risk(),
+        )
+    }
+}
+
+impl Display for Plan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.migrations.is_empty() {
+            return write!(f, "plan: no pending migrations");
+        }
+
+        write!(
+            f,
+            "plan: {} migration(s), {} statement(s), highest risk: {}",
+            self.migrations.len(),
+            self.statements(),
+            self.risk()
+        )?;
+
+        let mut order = 0;
+        for migration in &self.migrations {
+            write!(f, "\n{} [{} risk]", migration.migration, migration.risk())?;
+            if migration.statements.is_empty() {
+                write!(f, "\n    (no statements)")?;
+            }
+            for statement in &migration.statements {
+                order += 1;
+                let wait = if is_schema_change(statement) { ", waits for schema agreement" } else { "" };
+                write!(f, "\n    {order}. {} [{} risk{wait}]", statement.summary(), statement.risk())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) fn plan(planned: Vec<PlannedMigration>) -> Plan {
+    let migrations = planned
+        .into_iter()
+        .map(|planned| MigrationPlan {
+            statements: planned.statements.iter().map(|s| cql::parse(s)).collect(),
+            migration: planned.migration,
+            file: planned.file,
+        })
+        .collect();
+
+    Plan { migrations }
+}
+
+pub(crate) fn is_schema_change(statement: &Statement) -> bool {
+    matches!(statement.action, Action::Create | Action::Alter | Action::Drop)
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
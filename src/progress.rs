@@ -0,0 +1,123 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Snapshot of a running `up` or `down`.
+#[derive(Debug, Clone)]
+pub struct Progress {
+    /// Migration currently being applied or reverted.
+    pub migration: String,
+    pub migrations_done: usize,
+    pub migrations_total: usize,
+    pub statements_done: usize,
+    pub statements_total: usize,
+    pub elapsed: Duration,
+    /// Estimated from the average statement time of the earlier recorded runs and of this one so far.
+    /// `None` until a statement completes, when nothing was recorded before.
+    pub remaining: Option<Duration>,
+}
+
+/// Receives progress updates after every statement and migration of a run.
+pub trait Observer: Send + Sync {
+    fn on_progress(&self, progress: &Progress);
+}
+
+pub(crate) struct ProgressTracker {
+    observer: Option<Arc<dyn Observer>>,
+    started: Instant,
+    /// Time the statements of earlier recorded runs took, and how many they were.
+    history: (Duration, usize),
+    progress: Progress,
+}
+
+impl ProgressTracker {
+    pub(crate) fn new(observer: Option<Arc<dyn Observer>>, migrations_total: usize, statements_total: usize) -> Self {
+        ProgressTracker {
+            observer,
+            started: Instant::now(),
+            history: (Duration::ZERO, 0),
+            progress: Progress {
+                migration: String::new(),
+                migrations_done: 0,
+                migrations_total,
+                statements_done: 0,
+                statements_total,
+                elapsed: Duration::ZERO,
+                remaining: None,
+            },
+        }
+    }
+
+    /// Seeds the estimate with `statements` of earlier runs, which took `duration`, so the first statements of this
+    /// run don't make it swing wildly.
+    pub(crate) fn with_history(mut self, duration: Duration, statements: usize) -> Self {
+        self.history = (duration, statements);
+        self
+    }
+
+    pub(crate) fn start_migration(&mut self, migration: &str) {
+        self.progress.migration = migration.to_string();
+        self.notify();
+    }
+
+    pub(crate) fn statement_done(&mut self) {
+        self.progress.statements_done += 1;
+        self.notify();
+    }
+
+    pub(crate) fn migration_done(&mut self) {
+        self.progress.migrations_done += 1;
+        self.notify();
+    }
+
+    fn notify(&mut self) {
+        let Some(observer) = &self.observer else {
+            return;
+        };
+
+        let progress = &mut self.progress;
+        progress.elapsed = self.started.elapsed();
+        let (history, history_statements) = self.history;
+        let statements = history_statements + progress.statements_done;
+        progress.remaining = if statements == 0 {
+            None
+        } else {
+            let left = progress.statements_total.saturating_sub(progress.statements_done) as u32;
+            Some((history + progress.elapsed) / statements as u32 * left)
+        };
+
+        observer.on_progress(progress);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Last(Mutex<Option<Progress>>);
+
+    impl Observer for Last {
+        fn on_progress(&self, progress: &Progress) {
+            *self.0.lock().unwrap() = Some(progress.clone());
+        }
+    }
+
+    #[test]
+    fn no_estimate_without_history() {
+        let last = Arc::new(Last::default());
+        let mut tracker = ProgressTracker::new(Some(last.clone()), 1, 4);
+        tracker.start_migration("20240101000000_a");
+        assert_eq!(last.0.lock().unwrap().as_ref().unwrap().remaining, None);
+    }
+
+    #[test]
+    fn estimate_seeded_from_history() {
+        let last = Arc::new(Last::default());
+        let mut tracker = ProgressTracker::new(Some(last.clone()), 1, 4).with_history(Duration::from_secs(20), 10);
+        tracker.start_migration("20240101000000_a");
+        let remaining = last.0.lock().unwrap().as_ref().unwrap().remaining.unwrap();
+        // 2s per statement, give or take the few microseconds elapsed
+        assert!(remaining >= Duration::from_secs(8) && remaining < Duration::from_secs(9));
+    }
+}
@@ -0,0 +1,234 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use scylla::transport::session::{AddressTranslator, TranslationError};
+use scylla::transport::topology::UntranslatedPeer;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{copy_bidirectional, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::process::{Child, Command};
+use tokio::sync::Mutex;
+
+const DEFAULT_CQL_PORT: u16 = 9042;
+const SSH_READY_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Default, Clone)]
+pub struct ProxyOptions {
+    /// `host:port` of a SOCKS5 proxy.
+    pub socks5: String,
+    /// ssh destination (`user@bastion`) used as a jump host.
+    pub ssh_jump: String,
+}
+
+/// Routes every cluster connection through a SOCKS5 proxy.
+///
+/// The driver can only dial plain addresses, so each node gets a local listener that
+/// forwards its connections through the proxy, and peers discovered from the cluster are
+/// translated to their listener. An ssh jump host is handled by starting `ssh -D`, which
+/// exposes the bastion as a local SOCKS5 proxy.
+pub(crate) struct Proxy {
+    socks5: String,
+    port: u16,
+    forwards: Mutex<HashMap<String, SocketAddr>>,
+    _ssh: Option<Child>,
+}
+
+impl Proxy {
+    pub(crate) async fn start(options: &ProxyOptions, db_url: &str) -> Result<Option<Proxy>> {
+        if !options.socks5.is_empty() && !options.ssh_jump.is_empty() {
+            return Err(anyhow::anyhow!("Either a SOCKS5 proxy or an ssh jump host can be used, not both"));
+        }
+
+        let port = host_port(db_url).map_or(DEFAULT_CQL_PORT, |(_, port)| port);
+
+        let (socks5, ssh) = if !options.socks5.is_empty() {
+            (options.socks5.clone(), None)
+        } else if !options.ssh_jump.is_empty() {
+            let (socks5, child) = ssh_socks5(&options.ssh_jump).await?;
+            (socks5, Some(child))
+        } else {
+            return Ok(None);
+        };
+
+        Ok(Some(Proxy { socks5, port, forwards: Mutex::new(HashMap::new()), _ssh: ssh }))
+    }
+
+    /// Returns the local address forwarding to `target` (`host:port`), starting a listener if needed.
+    pub(crate) async fn local_address(&self, target: &str) -> Result<SocketAddr> {
+        let mut forwards = self.forwards.lock().await;
+        if let Some(address) = forwards.get(target) {
+            return Ok(*address);
+        }
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let address = listener.local_addr()?;
+        let socks5 = self.socks5.clone();
+        let remote = target.to_string();
+
+        tokio::spawn(async move {
+            while let Ok((mut inbound, _)) = listener.accept().await {
+                let socks5 = socks5.clone();
+                let remote = remote.clone();
+                tokio::spawn(async move {
+                    match socks5_connect(&socks5, &remote).await {
+                        Ok(mut outbound) => {
+                            _ = copy_bidirectional(&mut inbound, &mut outbound).await;
+                        }
+                        Err(e) => log::warn!("proxy: unable to reach [{remote}] through [{socks5}]: {e}"),
+                    }
+                });
+            }
+        });
+
+        forwards.insert(target.to_string(), address);
+        Ok(address)
+    }
+}
+
+#[async_trait]
+impl AddressTranslator for Proxy {
+    async fn translate_address(&self, untranslated_peer: &UntranslatedPeer) -> Result<SocketAddr, TranslationError> {
+        // Peers are reported with the port of the connection they were discovered on, which is
+        // a local listener, so the target port is taken from the contact point instead.
+        let target = SocketAddr::new(untranslated_peer.untranslated_address.ip(), self.port);
+        self.local_address(&target.to_string())
+            .await
+            .map_err(|e| {
+                log::warn!("proxy: unable to forward [{target}]: {e}");
+                TranslationError::NoRuleForAddress
+            })
+    }
+}
+
+/// Starts `ssh -D` against the jump host and waits until its SOCKS5 port accepts connections.
+async fn ssh_socks5(destination: &str) -> Result<(String, Child)> {
+    let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?.local_addr()?.port();
+    let socks5 = format!("127.0.0.1:{port}");
+
+    let mut child = Command::new("ssh")
+        .args(["-N", "-o", "ExitOnForwardFailure=yes", "-D", socks5.as_str(), destination])
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("Unable to start ssh to [{destination}]: {e}"))?;
+
+    let started = tokio::time::Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Err(anyhow::anyhow!("ssh to [{destination}] exited with {status}"));
+        }
+
+        if TcpStream::connect(socks5.as_str()).await.is_ok() {
+            return Ok((socks5, child));
+        }
+
+        if started.elapsed() > SSH_READY_TIMEOUT {
+            return Err(anyhow::anyhow!("Timed out waiting for ssh tunnel to [{destination}]"));
+        }
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+}
+
+/// Host and port of a contact point: `host:port`, `[ipv6]:port`, or a host or IP without port, on the CQL port.
+fn host_port(target: &str) -> Result<(&str, u16)> {
+    let invalid_port = |port: &str| anyhow::anyhow!("Invalid port [{port}] in [{target}]");
+    if let Some(rest) = target.strip_prefix('[') {
+        let (host, rest) = rest.split_once(']').ok_or_else(|| anyhow::anyhow!("Unclosed [ in [{target}]"))?;
+        return match rest.strip_prefix(':') {
+            Some(port) => Ok((host, port.parse().map_err(|_| invalid_port(port))?)),
+            None if rest.is_empty() => Ok((host, DEFAULT_CQL_PORT)),
+            None => Err(anyhow::anyhow!("Unexpected [{rest}] in [{target}]")),
+        };
+    }
+    if target.parse::<IpAddr>().is_ok() {
+        return Ok((target, DEFAULT_CQL_PORT));
+    }
+
+    match target.rsplit_once(':') {
+        Some((host, port)) => Ok((host, port.parse().map_err(|_| invalid_port(port))?)),
+        None => Ok((target, DEFAULT_CQL_PORT)),
+    }
+}
+
+/// Opens a connection to `target` (`host:port`, the port defaulting to the CQL one) through a SOCKS5 proxy without
+/// authentication.
+async fn socks5_connect(proxy: &str, target: &str) -> Result<TcpStream> {
+    let (host, port) = host_port(target)?;
+    let host_len = u8::try_from(host.len())
+        .map_err(|_| anyhow::anyhow!("Host name [{host}] is longer than the 255 bytes SOCKS5 allows"))?;
+
+    let mut stream = TcpStream::connect(proxy).await?;
+
+    stream.write_all(&[5, 1, 0]).await?;
+    let mut greeting = [0u8; 2];
+    stream.read_exact(&mut greeting).await?;
+    if greeting != [5, 0] {
+        return Err(anyhow::anyhow!("SOCKS5 proxy refused the connection without authentication"));
+    }
+
+    let mut request = vec![5, 1, 0];
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(1);
+            request.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            request.push(4);
+            request.extend_from_slice(&ip.octets());
+        }
+        Err(_) => {
+            request.push(3);
+            request.push(host_len);
+            request.extend_from_slice(host.as_bytes());
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await?;
+
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await?;
+    if reply[1] != 0 {
+        return Err(anyhow::anyhow!("SOCKS5 proxy failed to connect, reply code {}", reply[1]));
+    }
+
+    let address_len = match reply[3] {
+        1 => 4,
+        4 => 16,
+        3 => stream.read_u8().await? as usize,
+        other => return Err(anyhow::anyhow!("SOCKS5 proxy replied with unknown address type {other}")),
+    };
+    let mut bound = vec![0u8; address_len + 2];
+    stream.read_exact(&mut bound).await?;
+
+    Ok(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contact_points_with_port() {
+        assert_eq!(host_port("10.0.0.1:9142").unwrap(), ("10.0.0.1", 9142));
+        assert_eq!(host_port("scylla.internal:19042").unwrap(), ("scylla.internal", 19042));
+        assert_eq!(host_port("[::1]:9142").unwrap(), ("::1", 9142));
+    }
+
+    #[test]
+    fn contact_points_without_port() {
+        assert_eq!(host_port("10.0.0.1").unwrap(), ("10.0.0.1", DEFAULT_CQL_PORT));
+        assert_eq!(host_port("scylla.internal").unwrap(), ("scylla.internal", DEFAULT_CQL_PORT));
+        assert_eq!(host_port("::1").unwrap(), ("::1", DEFAULT_CQL_PORT));
+        assert_eq!(host_port("[::1]").unwrap(), ("::1", DEFAULT_CQL_PORT));
+    }
+
+    #[test]
+    fn invalid_contact_points() {
+        assert!(host_port("host:port").is_err());
+        assert!(host_port("[::1").is_err());
+        assert!(host_port("[::1]9042").is_err());
+    }
+}
@@ -0,0 +1,212 @@
+use crate::cql::{self, Action};
+use crate::local;
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+
+/// State of a migration's down.cql.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DownFile {
+    Present,
+    Empty,
+    Missing,
+    /// Declared `no_down: true`. Crossing it only marks the tracker row rolled back.
+    Irreversible,
+}
+
+/// Ordered downs needed to get back to `target`.
+#[derive(Debug)]
+pub struct RollbackPlan {
+    pub target: String,
+    /// Newest first, which is the execution order.
+    pub steps: Vec<(String, DownFile)>,
+}
+
+impl RollbackPlan {
+    pub fn missing_downs(&self) -> Vec<&str> {
+        self.with(DownFile::Missing)
+    }
+
+    pub fn irreversible(&self) -> Vec<&str> {
+        self.with(DownFile::Irreversible)
+    }
+
+    fn with(&self, state: DownFile) -> Vec<&str> {
+        self.steps
+            .iter()
+            .filter(|(_, down)| *down == state)
+            .map(|(migration, _)| migration.as_str())
+            .collect()
+    }
+
+    /// Migrations to pass to `Migrator::revert`, oldest first.
+    pub fn migrations(&self) -> Vec<String> {
+        self.steps.iter().rev().map(|(migration, _)| migration.clone()).collect()
+    }
+}
+
+impl Display for RollbackPlan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "rollback to [{}]:", self.target)?;
+        if self.steps.is_empty() {
+            return write!(f, "    nothing to revert");
+        }
+
+        for (i, (migration, down)) in self.steps.iter().enumerate() {
+            let note = match down {
+                DownFile::Present => "",
+                DownFile::Empty => " (empty down.cql, only the tracker row is removed)",
+                DownFile::Missing => " (NO down.cql)",
+                DownFile::Irreversible => " (declared no_down, nothing is executed, only the tracker row is removed)",
+            };
+            write!(f, "    {}. revert {migration}{note}", i + 1)?;
+            if i + 1 < self.steps.len() {
+                writeln!(f)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether a migration can be reverted, as far as its down.cql tells.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    Reversible,
+    MissingDown,
+    EmptyDown,
+    /// Declared `no_down: true`.
+    Irreversible,
+    /// Statement number and text the parser could not make sense of.
+    Unparseable(usize, String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Reversibility {
+    pub migration: String,
+    pub status: Status,
+}
+
+impl Reversibility {
+    pub fn is_reversible(&self) -> bool {
+        self.status == Status::Reversible
+    }
+}
+
+impl Display for Reversibility {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.status {
+            Status::Reversible => write!(f, "ok: {}", self.migration),
+            Status::MissingDown => write!(f, "not reversible: {} has no down.cql", self.migration),
+            Status::EmptyDown => write!(f, "not reversible: {} has an empty down.cql", self.migration),
+            Status::Irreversible => write!(f, "irreversible: {} is declared no_down", self.migration),
+            Status::Unparseable(index, statement) => write!(
+                f,
+                "not reversible: {} down.cql statement #{index} is not recognized: {statement}",
+                self.migration
+            ),
+        }
+    }
+}
+
+pub(crate) fn down_file(dir_path: &str, migration: &str) -> DownFile {
+    if local::is_irreversible(dir_path, migration) {
+        return DownFile::Irreversible;
+    }
+
+    if local::parts(&local::file_path(dir_path, migration, "down.cql")).is_empty() {
+        DownFile::Missing
+    } else if local::has_migration_content(&local::file_path(dir_path, migration, "down.cql")) {
+        DownFile::Present
+    } else {
+        DownFile::Empty
+    }
+}
+
+pub(crate) fn check(dir_path: &str, migration: &str) -> Status {
+    match down_file(dir_path, migration) {
+        DownFile::Missing => return Status::MissingDown,
+        DownFile::Empty => return Status::EmptyDown,
+        DownFile::Irreversible => return Status::Irreversible,
+        DownFile::Present => {}
+    }
+
+    let Ok(down) = local::migration_file(&local::file_path(dir_path, migration, "down.cql")) else {
+        return Status::MissingDown;
+    };
+
+    let statements = cql::parse_all(&down);
+    if statements.is_empty() {
+        // only comments
+        return Status::EmptyDown;
+    }
+
+    for (index, statement) in statements.iter().enumerate() {
+        let recognized = match statement.action {
+            Action::Other => false,
+            Action::Create | Action::Alter | Action::Drop => statement.kind.is_some() && statement.name.is_some(),
+            _ => true,
+        };
+        if !recognized {
+            return Status::Unparseable(index + 1, statement.text.clone());
+        }
+    }
+
+    Status::Reversible
+}
+
+/// Result of applying a migration, reverting it and comparing the schema.
+#[derive(Debug, Clone)]
+pub enum Outcome {
+    Passed,
+    /// The schema after the down differs from the schema before the up.
+    Failed { left_behind: Vec<String>, lost: Vec<String> },
+    Skipped(String),
+    /// The up or the down failed to execute.
+    Error(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct RoundTrip {
+    pub migration: String,
+    pub outcome: Outcome,
+}
+
+impl RoundTrip {
+    pub fn passed(&self) -> bool {
+        matches!(self.outcome, Outcome::Passed | Outcome::Skipped(_))
+    }
+}
+
+impl Display for RoundTrip {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.outcome {
+            Outcome::Passed => write!(f, "PASS {}", self.migration),
+            Outcome::Skipped(reason) => write!(f, "SKIP {}: {reason}", self.migration),
+            Outcome::Error(error) => write!(f, "ERROR {}: {error}", self.migration),
+            Outcome::Failed { left_behind, lost } => {
+                write!(f, "FAIL {}: the down does not restore the schema", self.migration)?;
+                for statement in left_behind {
+                    write!(f, "\n    left behind: {statement}")?;
+                }
+                for statement in lost {
+                    write!(f, "\n    lost: {statement}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Compares two `describe` snapshots, statement by statement.
+pub(crate) fn compare(before: &[String], after: &[String]) -> Outcome {
+    let before: BTreeSet<&str> = before.iter().map(|s| s.trim()).collect();
+    let after: BTreeSet<&str> = after.iter().map(|s| s.trim()).collect();
+
+    let left_behind: Vec<String> = after.difference(&before).map(|s| s.to_string()).collect();
+    let lost: Vec<String> = before.difference(&after).map(|s| s.to_string()).collect();
+    if left_behind.is_empty() && lost.is_empty() {
+        return Outcome::Passed;
+    }
+
+    Outcome::Failed { left_behind, lost }
+}
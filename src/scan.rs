@@ -0,0 +1,65 @@
+use crate::cql::Name;
+use openssl::sha::sha256;
+use std::time::Duration;
+use tokio::time::{Interval, MissedTickBehavior};
+
+/// How a full table scan is split up.
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    /// Token ranges the ring is split into. Also the resume granularity: progress is kept per
+    /// number of ranges, so it must stay the same between an interrupted run and its resume.
+    pub ranges: usize,
+    /// Ranges scanned at once.
+    pub parallelism: usize,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions { ranges: 256, parallelism: 4 }
+    }
+}
+
+/// The Murmur3 token ring split into `count` contiguous, inclusive ranges.
+pub(crate) fn token_ranges(count: usize) -> Vec<(i64, i64)> {
+    let count = count.max(1) as i128;
+    let (min, max) = (i64::MIN as i128, i64::MAX as i128);
+    let step = (max - min + 1) / count;
+
+    (0..count)
+        .map(|i| {
+            let start = min + i * step;
+            let end = if i == count - 1 { max } else { start + step - 1 };
+            (start as i64, end as i64)
+        })
+        .collect()
+}
+
+/// Name under which the progress of a scan is kept: what it does and to which table,
+/// plus a digest of `detail`, e.g. the statement, when the same table can be scanned for different reasons.
+pub(crate) fn scan_name(kind: &str, table: &Name, detail: &str) -> String {
+    if detail.is_empty() {
+        return format!("{kind}:{table}");
+    }
+
+    let digest: String = sha256(detail.as_bytes())[..4].iter().map(|b| format!("{b:02x}")).collect();
+    format!("{kind}:{table}:{digest}")
+}
+
+/// Spaces out writes to at most `per_second`. Without a rate, doesn't wait.
+pub(crate) struct RateLimiter(Option<Interval>);
+
+impl RateLimiter {
+    pub(crate) fn new(per_second: Option<u32>) -> RateLimiter {
+        RateLimiter(per_second.filter(|rate| *rate > 0).map(|rate| {
+            let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / rate as f64));
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            interval
+        }))
+    }
+
+    pub(crate) async fn wait(&mut self) {
+        if let Some(interval) = &mut self.0 {
+            interval.tick().await;
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use crate::cdc;
+use crate::cql::{self, Action, Name, ObjectKind, Statement};
+use crate::db;
+use crate::drift;
+use crate::local;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+/// Frontmatter key listing the migrations applied when the snapshot was taken.
+const APPLIED: &str = "applied";
+
+/// The schema and the applied migrations at a point in time, e.g. right before a run.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub applied: Vec<String>,
+    /// CQL definitions, as printed by `describe`.
+    pub schema: String,
+}
+
+impl Snapshot {
+    pub fn parse(contents: &str) -> Snapshot {
+        let applied = local::frontmatter(contents)
+            .into_iter()
+            .find(|(key, _)| key == APPLIED)
+            .map(|(_, value)| value.split(',').map(|m| m.trim().to_string()).filter(|m| !m.is_empty()).collect())
+            .unwrap_or_default();
+
+        Snapshot { applied, schema: contents.to_string() }
+    }
+
+    /// Snapshot file contents: a plain CQL schema dump, with the applied migrations as frontmatter.
+    pub fn render(&self) -> String {
+        format!("-- {APPLIED}: {}\n\n{}\n", self.applied.join(", "), self.schema.trim())
+    }
+}
+
+/// Schema level downgrade from the live schema back to a snapshot.
+#[derive(Debug, Default)]
+pub struct Downgrade {
+    /// Executed in order: drops of objects created since, then creates of objects dropped since.
+    pub statements: Vec<String>,
+    /// Objects that exist on both sides with a different definition. Left to a human.
+    pub changed: Vec<(ObjectKind, Name)>,
+    /// Tables whose CDC was enabled or disabled since the snapshot. Also in `changed`.
+    pub cdc_changed: Vec<Name>,
+    /// Applied migrations missing from the snapshot, whose tracker rows are removed.
+    pub forget: Vec<String>,
+}
+
+impl Downgrade {
+    pub fn is_empty(&self) -> bool {
+        self.statements.is_empty() && self.changed.is_empty() && self.forget.is_empty()
+    }
+}
+
+impl Display for Downgrade {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return write!(f, "the schema matches the snapshot");
+        }
+
+        writeln!(f, "statements to execute: {}", self.statements.len())?;
+        for (i, statement) in self.statements.iter().enumerate() {
+            writeln!(f, "    #{} {statement}", i + 1)?;
+        }
+
+        if !self.changed.is_empty() {
+            writeln!(f, "changed since the snapshot, not downgraded automatically:")?;
+            for (kind, name) in &self.changed {
+                let cdc = if self.cdc_changed.contains(name) { " (CDC enabled or disabled since, log consumers are affected)" } else { "" };
+                writeln!(f, "    {kind} {name}{cdc}")?;
+            }
+        }
+
+        write!(f, "tracker rows to remove: {:?}", self.forget)
+    }
+}
+
+/// Drop order: dependents before what they depend on.
+const DROP_ORDER: [ObjectKind; 7] = [
+    ObjectKind::MaterializedView,
+    ObjectKind::Index,
+    ObjectKind::Aggregate,
+    ObjectKind::Function,
+    ObjectKind::Table,
+    ObjectKind::Type,
+    ObjectKind::Keyspace,
+];
+
+/// Compares the live schema with `snapshot`. Objects created since are dropped, objects dropped
+/// since are re-created from the snapshot, in the order the snapshot defines them.
+pub(crate) fn downgrade(snapshot: &Snapshot, live_schema: &str, applied: &[String]) -> Downgrade {
+    let then = objects(&snapshot.schema);
+    let now = objects(live_schema);
+    let mut downgrade = Downgrade::default();
+
+    let dropped_keyspaces: Vec<&str> = now
+        .keys()
+        .filter(|(kind, name)| *kind == ObjectKind::Keyspace && !then.contains_key(&(*kind, name.clone())))
+        .map(|(_, name)| name.name.as_str())
+        .collect();
+
+    for kind in DROP_ORDER {
+        for (key, _) in now.iter().filter(|(key, _)| key.0 == kind && !then.contains_key(key)) {
+            let (kind, name) = key;
+            // dropping the keyspace takes everything in it
+            if kind != &ObjectKind::Keyspace && name.keyspace.as_deref().is_some_and(|k| dropped_keyspaces.contains(&k)) {
+                continue;
+            }
+            downgrade.statements.push(drift::drop_statement(*kind, name));
+        }
+    }
+
+    let mut recreated: Vec<&(usize, Statement)> = then.iter().filter(|(key, _)| !now.contains_key(key)).map(|(_, s)| s).collect();
+    recreated.sort_by_key(|(position, _)| *position);
+    downgrade.statements.extend(recreated.into_iter().map(|(_, s)| s.text.trim_end_matches(';').to_string()));
+
+    for (key, (_, statement)) in &then {
+        if let Some((_, live)) = now.get(key) {
+            if normalize(&statement.text) != normalize(&live.text) {
+                downgrade.changed.push(key.clone());
+                if cdc::enabled(&statement.text).unwrap_or(false) != cdc::enabled(&live.text).unwrap_or(false) {
+                    downgrade.cdc_changed.push(key.1.clone());
+                }
+            }
+        }
+    }
+
+    downgrade.forget = applied.iter().filter(|m| !snapshot.applied.contains(m)).rev().cloned().collect();
+    downgrade
+}
+
+/// `CREATE` statements by object, with their position in the schema.
+fn objects(schema: &str) -> BTreeMap<(ObjectKind, Name), (usize, Statement)> {
+    cql::parse_all(schema)
+        .into_iter()
+        .filter(|s| s.action == Action::Create)
+        .enumerate()
+        .filter_map(|(position, statement)| {
+            let kind = statement.kind?;
+            let mut name = statement.name.clone()?;
+            if name.keyspace.is_none() && kind != ObjectKind::Keyspace {
+                name.keyspace = statement.base.as_ref().and_then(|b| b.keyspace.clone());
+            }
+
+            // created and dropped along with the cdc option of its base table
+            if kind == ObjectKind::Table && cdc::base_table(&name).is_some() {
+                return None;
+            }
+
+            let keyspace = if kind == ObjectKind::Keyspace { Some(name.name.as_str()) } else { name.keyspace.as_deref() };
+            if keyspace == Some(db::keyspace()) {
+                return None;
+            }
+
+            Some(((kind, name), (position, statement)))
+        })
+        .collect()
+}
+
+fn normalize(statement: &str) -> String {
+    statement.trim().trim_end_matches(';').split_whitespace().collect::<Vec<_>>().join(" ")
+}
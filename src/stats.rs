@@ -0,0 +1,93 @@
+use crate::cql::{self, Action, Name, ObjectKind};
+use crate::local;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+/// Size and tombstone figures of a table pending migrations touch, from the system tables.
+/// Estimates and large partitions are those of the node answering, not the whole cluster.
+#[derive(Debug, Clone)]
+pub struct TableStats {
+    pub table: Name,
+    /// Pending migrations touching the table.
+    pub migrations: Vec<String>,
+    /// `false` when the table does not exist yet, in which case there is nothing else to report.
+    pub exists: bool,
+    pub partitions: Option<i64>,
+    pub bytes: Option<i64>,
+    pub gc_grace_seconds: Option<i64>,
+    pub default_ttl: Option<i64>,
+    /// Size of the partitions recorded in `system.large_partitions`.
+    pub large_partitions: Vec<i64>,
+    /// Range tombstones and dead rows of the large partitions. `None` on versions not recording them.
+    pub tombstones: Option<i64>,
+}
+
+impl Display for TableStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} (touched by {:?})", self.table, self.migrations)?;
+        if !self.exists {
+            return write!(f, "    does not exist yet");
+        }
+
+        let or_unknown = |value: Option<i64>, render: fn(i64) -> String| value.map(render).unwrap_or("unknown".to_string());
+        writeln!(
+            f,
+            "    partitions ~{}, size ~{}",
+            or_unknown(self.partitions, |p| p.to_string()),
+            or_unknown(self.bytes, bytes)
+        )?;
+        match self.large_partitions.iter().max() {
+            Some(largest) => writeln!(f, "    large partitions {}, largest {}", self.large_partitions.len(), bytes(*largest))?,
+            None => writeln!(f, "    large partitions 0")?,
+        }
+        write!(
+            f,
+            "    tombstones in large partitions {}, gc_grace_seconds {}, default_time_to_live {}",
+            or_unknown(self.tombstones, |t| t.to_string()),
+            or_unknown(self.gc_grace_seconds, |s| s.to_string()),
+            or_unknown(self.default_ttl, |s| s.to_string())
+        )
+    }
+}
+
+fn bytes(bytes: i64) -> String {
+    let units = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", units[unit])
+}
+
+/// Tables the `pending` migrations create, alter, drop, index, or read and write, with the migrations touching them.
+/// Unqualified names, outside of a `USE`, are left out.
+pub(crate) fn touched(dir_path: &str, pending: &[String]) -> BTreeMap<Name, Vec<String>> {
+    let mut tables: BTreeMap<Name, Vec<String>> = BTreeMap::new();
+
+    for migration in pending {
+        let Ok(up) = local::migration_file(&local::file_path(dir_path, migration, "up.cql")) else {
+            continue;
+        };
+
+        for statement in cql::parse_all(&up) {
+            let table = match (statement.kind, statement.action) {
+                (Some(ObjectKind::Table), _) => statement.name,
+                (Some(ObjectKind::Index | ObjectKind::MaterializedView), _) => statement.base,
+                (None, Action::Insert | Action::Update | Action::Delete | Action::Select) => statement.name,
+                _ => None,
+            };
+            let Some(table) = table.filter(|t| t.keyspace.is_some()) else {
+                continue;
+            };
+
+            let migrations = tables.entry(table).or_default();
+            if !migrations.contains(migration) {
+                migrations.push(migration.clone());
+            }
+        }
+    }
+
+    tables
+}
@@ -0,0 +1,168 @@
+use crate::db::MigrationData;
+use chrono::{DateTime, Utc};
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Pending,
+    Applied,
+    Failed,
+    /// Applied, then reverted by `down`. Pending again.
+    RolledBack,
+}
+
+impl Display for State {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            State::Pending => "pending",
+            State::Applied => "applied",
+            State::Failed => "failed",
+            State::RolledBack => "rolled_back",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// Leading characters of a run id shown, enough to tell runs apart and to pass to `down --run`.
+const RUN_ID_SHOWN: usize = 8;
+
+/// Where a migration stands according to the tracker.
+#[derive(Debug, Clone)]
+pub struct MigrationStatus {
+    pub migration: String,
+    pub state: State,
+    /// When it was last applied, or last failed.
+    pub run_at: Option<DateTime<Utc>>,
+    /// How long its last run took, when it was run rather than only recorded.
+    pub duration: Option<Duration>,
+    /// OS user and machine of its last run, and the version of the tool, for audits.
+    pub applied_by: Option<String>,
+    pub hostname: Option<String>,
+    pub tool_version: Option<String>,
+    pub description: Option<String>,
+    /// The `up` run that applied it, shared by the migrations deployed together.
+    pub run_id: Option<Uuid>,
+    /// When it was reverted, for a rolled back migration. `run_at` and the rest still describe its apply.
+    pub rolled_back_at: Option<DateTime<Utc>>,
+    /// Why its last run failed, and the 1-based index and text of the statement it failed on, for a failed migration.
+    pub error: Option<String>,
+    pub error_statement: Option<usize>,
+    pub error_cql: Option<String>,
+    /// `false` for tracker rows whose migration directory is gone.
+    pub local: bool,
+}
+
+impl MigrationStatus {
+    fn pending(migration: &str) -> MigrationStatus {
+        MigrationStatus {
+            migration: migration.to_string(),
+            state: State::Pending,
+            run_at: None,
+            duration: None,
+            applied_by: None,
+            hostname: None,
+            tool_version: None,
+            description: None,
+            run_id: None,
+            rolled_back_at: None,
+            error: None,
+            error_statement: None,
+            error_cql: None,
+            local: true,
+        }
+    }
+
+    fn tracked(row: MigrationData, local: bool) -> MigrationStatus {
+        MigrationStatus {
+            state: match row.status.as_str() {
+                "success" => State::Applied,
+                "rolled_back" => State::RolledBack,
+                _ => State::Failed,
+            },
+            duration: row.duration_ms.map(|ms| Duration::from_millis(ms.max(0) as u64)),
+            migration: row.id,
+            run_at: row.run_at,
+            applied_by: row.applied_by,
+            hostname: row.hostname,
+            tool_version: row.tool_version,
+            description: row.description,
+            run_id: row.run_id,
+            rolled_back_at: row.rolled_back_at,
+            error: row.error,
+            error_statement: row.error_statement.map(|index| index.max(0) as usize),
+            error_cql: row.error_cql,
+            local,
+        }
+    }
+}
+
+impl Display for MigrationStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:<50} {:<11}", self.migration, self.state.to_string())?;
+        if let Some(run_at) = self.run_at {
+            write!(f, " {}", run_at.format("%Y-%m-%d %H:%M:%S UTC"))?;
+        }
+        if let Some(duration) = self.duration {
+            write!(f, " took {duration:?}")?;
+        }
+        match (&self.applied_by, &self.hostname) {
+            (Some(user), Some(hostname)) => write!(f, " by {user}@{hostname}")?,
+            (Some(who), None) | (None, Some(who)) => write!(f, " by {who}")?,
+            (None, None) => {}
+        }
+        if let Some(version) = &self.tool_version {
+            write!(f, " with v{version}")?;
+        }
+        if let Some(run_id) = self.run_id {
+            write!(f, " run {}", &run_id.to_string()[..RUN_ID_SHOWN])?;
+        }
+        if let Some(rolled_back_at) = self.rolled_back_at {
+            write!(f, ", rolled back {}", rolled_back_at.format("%Y-%m-%d %H:%M:%S UTC"))?;
+        }
+        if !self.local {
+            write!(f, " (missing locally)")?;
+        }
+        if let Some(description) = &self.description {
+            write!(f, ": {description}")?;
+        }
+        if self.state == State::Failed {
+            if let Some(index) = self.error_statement {
+                let cql = self.error_cql.as_deref().unwrap_or_default().split_whitespace().collect::<Vec<_>>().join(" ");
+                write!(f, "\n    failed at statement #{index}: {cql}")?;
+            }
+            if let Some(error) = &self.error {
+                write!(f, "\n    error: {error}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Every local migration, and every tracked one missing locally, in id order.
+pub(crate) fn statuses(local: &[String], rows: Vec<MigrationData>) -> Vec<MigrationStatus> {
+    let mut statuses: Vec<MigrationStatus> = local.iter().map(|migration| MigrationStatus::pending(migration)).collect();
+
+    for row in rows {
+        match statuses.iter_mut().find(|s| s.migration == row.id) {
+            Some(status) => *status = MigrationStatus::tracked(row, true),
+            None => statuses.push(MigrationStatus::tracked(row, false)),
+        }
+    }
+
+    statuses.sort_by(|a, b| a.migration.cmp(&b.migration));
+    statuses
+}
+
+/// Every tracker row, by run time, oldest first. Rows without a run time come first.
+pub(crate) fn history(local: &[String], mut rows: Vec<MigrationData>) -> Vec<MigrationStatus> {
+    rows.sort_by(|a, b| a.run_at.cmp(&b.run_at).then_with(|| a.id.cmp(&b.id)));
+    rows.into_iter()
+        .map(|row| {
+            let local = local.contains(&row.id);
+            MigrationStatus::tracked(row, local)
+        })
+        .collect()
+}
@@ -0,0 +1,325 @@
+use crate::cql::{self, Name, Statement, Token};
+
+/// A column of a `CREATE TABLE`.
+#[derive(Debug, Clone)]
+pub(crate) struct Column {
+    pub name: String,
+    /// Lowercased, without whitespace, e.g. `map<text,frozen<list<int>>>`.
+    pub cql_type: String,
+}
+
+/// The parts of a `CREATE TABLE` the lints look at.
+#[derive(Debug, Clone)]
+pub(crate) struct Table {
+    pub name: Name,
+    pub columns: Vec<Column>,
+    pub partition_key: Vec<String>,
+    pub clustering: Vec<String>,
+    /// `WITH` options by lowercased name, with the tokens of their value.
+    pub options: Vec<(String, Vec<Token>)>,
+}
+
+impl Column {
+    /// A collection that is not frozen, updated in place and unbounded.
+    pub fn is_unbounded_collection(&self) -> bool {
+        ["list<", "set<", "map<"].iter().any(|prefix| self.cql_type.starts_with(prefix))
+    }
+
+    pub fn is_blob(&self) -> bool {
+        self.cql_type == "blob"
+    }
+}
+
+impl Table {
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+
+    pub fn option(&self, name: &str) -> Option<&[Token]> {
+        self.options.iter().find(|(n, _)| n == name).map(|(_, value)| value.as_slice())
+    }
+
+    /// Clustered by time first, the shape of an append mostly time series.
+    pub fn is_time_series(&self) -> bool {
+        let first = self.clustering.first().and_then(|c| self.column(c));
+        first.is_some_and(|c| matches!(c.cql_type.as_str(), "timestamp" | "timeuuid" | "date"))
+    }
+}
+
+/// Parses a `CREATE TABLE` statement. `None` for any other statement.
+pub(crate) fn parse(statement: &Statement) -> Option<Table> {
+    if statement.action != cql::Action::Create || statement.kind != Some(cql::ObjectKind::Table) {
+        return None;
+    }
+
+    let tokens = cql::tokenize(&statement.text);
+    let open = tokens.iter().position(|t| *t == Token::Symbol('('))?;
+    let close = open + closing(&tokens[open..])?;
+
+    let mut table = Table {
+        name: statement.name.clone()?,
+        columns: vec![],
+        partition_key: vec![],
+        clustering: vec![],
+        options: vec![],
+    };
+
+    for element in split(&tokens[open + 1..close], ",") {
+        if is_word(element.first(), "PRIMARY") {
+            primary_key(&element[2..], &mut table);
+            continue;
+        }
+
+        let Some(name) = element.first().and_then(identifier) else {
+            continue;
+        };
+        let end = element.iter().position(|t| is_word(Some(t), "STATIC") || is_word(Some(t), "PRIMARY")).unwrap_or(element.len());
+        if element[end..].iter().any(|t| is_word(Some(t), "PRIMARY")) {
+            table.partition_key = vec![name.clone()];
+        }
+        table.columns.push(Column { name, cql_type: render(&element[1..end]) });
+    }
+
+    let rest = &tokens[close + 1..];
+    if is_word(rest.first(), "WITH") {
+        table.options = options(&rest[1..]);
+    }
+
+    Some(table)
+}
+
+/// The options of a `WITH` clause, e.g. of an `ALTER TABLE`. `tokens` starts after `WITH`.
+pub(crate) fn options(tokens: &[Token]) -> Vec<(String, Vec<Token>)> {
+    split(tokens, "AND")
+        .into_iter()
+        .filter_map(|option| {
+            let name = match option.first()? {
+                Token::Word(word) if word.eq_ignore_ascii_case("CLUSTERING") => return Some(("clustering order".to_string(), option[3..].to_vec())),
+                Token::Word(word) => word.to_lowercase(),
+                _ => return None,
+            };
+            let value = option.iter().position(|t| *t == Token::Symbol('=')).map(|i| option[i + 1..].to_vec()).unwrap_or_default();
+            Some((name, value))
+        })
+        .collect()
+}
+
+/// The `WITH` options of an `ALTER TABLE` statement.
+pub(crate) fn altered_options(statement: &Statement) -> Vec<(String, Vec<Token>)> {
+    if statement.action != cql::Action::Alter || statement.alteration.as_deref() != Some("WITH") {
+        return vec![];
+    }
+
+    let tokens = cql::tokenize(&statement.text);
+    match tokens.iter().position(|t| is_word(Some(t), "WITH")) {
+        Some(start) => options(&tokens[start + 1..]),
+        None => vec![],
+    }
+}
+
+/// Columns added by an `ALTER TABLE ... ADD`, either one or a parenthesized list.
+pub(crate) fn added_columns(statement: &Statement) -> Vec<Column> {
+    if statement.action != cql::Action::Alter || statement.alteration.as_deref() != Some("ADD") {
+        return vec![];
+    }
+
+    let tokens = cql::tokenize(&statement.text);
+    let Some(start) = tokens.iter().position(|t| is_word(Some(t), "ADD")) else {
+        return vec![];
+    };
+    let mut rest = &tokens[start + 1..];
+    if rest.first() == Some(&Token::Symbol('(')) {
+        rest = &rest[1..closing(rest).unwrap_or(rest.len())];
+    }
+
+    split(rest, ",")
+        .into_iter()
+        .filter_map(|element| {
+            let name = identifier(element.first()?)?;
+            let end = element.iter().position(|t| is_word(Some(t), "STATIC")).unwrap_or(element.len());
+            Some(Column { name, cql_type: render(&element[1..end]) })
+        })
+        .collect()
+}
+
+/// Column a global `CREATE INDEX` indexes, also through `keys(...)`, `values(...)`, `entries(...)` or `full(...)`.
+/// `None` for local indexes, which are bounded by their partition.
+pub(crate) fn indexed_column(statement: &Statement) -> Option<String> {
+    if statement.action != cql::Action::Create || statement.kind != Some(cql::ObjectKind::Index) {
+        return None;
+    }
+
+    let tokens = cql::tokenize(&statement.text);
+    let on = tokens.iter().position(|t| is_word(Some(t), "ON"))?;
+    let open = on + tokens[on..].iter().position(|t| *t == Token::Symbol('('))?;
+    let inner = &tokens[open + 1..open + closing(&tokens[open..])?];
+    if inner.first() == Some(&Token::Symbol('(')) {
+        return None;
+    }
+
+    inner
+        .iter()
+        .filter(|t| !["KEYS", "VALUES", "ENTRIES", "FULL"].iter().any(|k| is_word(Some(t), k)))
+        .find_map(identifier)
+}
+
+/// Identifiers as written where a DDL statement names something: the object, its keyspace,
+/// and the columns or fields it defines, adds or renames to.
+pub(crate) fn defined_identifiers(statement: &Statement) -> Vec<Token> {
+    use cql::{Action, ObjectKind};
+
+    let Some(kind) = statement.kind else {
+        return vec![];
+    };
+    if !matches!(statement.action, Action::Create | Action::Alter | Action::Drop) || matches!(kind, ObjectKind::Role | ObjectKind::Trigger) {
+        return vec![];
+    }
+
+    let tokens = cql::tokenize(&statement.text);
+    let skip = |pos: usize, keywords: &[&str]| {
+        let all = keywords.iter().enumerate().all(|(i, keyword)| is_word(tokens.get(pos + i), keyword));
+        if all { pos + keywords.len() } else { pos }
+    };
+    let pos = skip(skip(skip(1, &["OR", "REPLACE"]), &["CUSTOM"]), &["MATERIALIZED"]) + 1;
+    let pos = skip(skip(pos, &["IF", "NOT", "EXISTS"]), &["IF", "EXISTS"]);
+
+    let mut identifiers = vec![];
+    if !is_word(tokens.get(pos), "ON") {
+        identifiers.extend(tokens.get(pos).cloned());
+        if tokens.get(pos + 1) == Some(&Token::Symbol('.')) {
+            identifiers.extend(tokens.get(pos + 2).cloned());
+        }
+    }
+
+    let definitions = match (statement.action, kind) {
+        (Action::Create, ObjectKind::Table | ObjectKind::Type) => {
+            let open = tokens.iter().position(|t| *t == Token::Symbol('('));
+            open.and_then(|open| Some(&tokens[open + 1..open + closing(&tokens[open..])?]))
+        }
+        (Action::Alter, _) => match statement.alteration.as_deref() {
+            Some("ADD") => tokens.iter().position(|t| is_word(Some(t), "ADD")).map(|add| {
+                let rest = &tokens[add + 1..];
+                match rest.first() {
+                    Some(Token::Symbol('(')) => &rest[1..closing(rest).unwrap_or(rest.len())],
+                    _ => rest,
+                }
+            }),
+            Some("RENAME") => {
+                let renamed = tokens.windows(2).filter(|w| is_word(Some(&w[0]), "TO")).map(|w| w[1].clone());
+                identifiers.extend(renamed);
+                None
+            }
+            _ => None,
+        },
+        _ => None,
+    };
+    if let Some(definitions) = definitions {
+        let elements = split(definitions, ",").into_iter().filter(|e| !is_word(e.first(), "PRIMARY"));
+        identifiers.extend(elements.filter_map(|e| e.first().cloned()));
+    }
+
+    identifiers.retain(|t| matches!(t, Token::Word(_) | Token::Quoted(_)));
+    identifiers
+}
+
+/// A comment on the line defining `column`, or on the line right above, e.g. explaining why it may grow large.
+pub(crate) fn has_comment(text: &str, column: &str) -> bool {
+    let lines: Vec<&str> = text.lines().collect();
+
+    lines.iter().enumerate().any(|(i, line)| {
+        let comment = line.find("--").or_else(|| line.find("//"));
+        let code = &line[..comment.unwrap_or(line.len())];
+        let defines = cql::tokenize(code).first().and_then(identifier).is_some_and(|name| name == column);
+        let above = i > 0 && {
+            let previous = lines[i - 1].trim_start();
+            previous.starts_with("--") || previous.starts_with("//")
+        };
+
+        defines && (comment.is_some() || above)
+    })
+}
+
+fn primary_key(tokens: &[Token], table: &mut Table) {
+    let Some(close) = closing(tokens) else {
+        return;
+    };
+    let inner = &tokens[1..close];
+
+    let mut columns = split(inner, ",").into_iter();
+    match columns.next() {
+        Some([Token::Symbol('('), partition @ .., Token::Symbol(')')]) => {
+            table.partition_key = partition.iter().filter_map(identifier).collect();
+        }
+        Some(first) => table.partition_key = first.iter().filter_map(identifier).collect(),
+        None => return,
+    }
+    table.clustering = columns.filter_map(|c| c.first().and_then(identifier)).collect();
+}
+
+/// Index, relative to `tokens`, of the symbol closing the bracket `tokens` starts with.
+fn closing(tokens: &[Token]) -> Option<usize> {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Symbol('(' | '<' | '{' | '[') => depth += 1,
+            Token::Symbol(')' | '>' | '}' | ']') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Splits on a top level `separator`, a symbol or a keyword.
+fn split<'a>(tokens: &'a [Token], separator: &str) -> Vec<&'a [Token]> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Symbol('(' | '<' | '{' | '[') => depth += 1,
+            Token::Symbol(')' | '>' | '}' | ']') => depth -= 1,
+            Token::Symbol(c) if depth == 0 && separator.len() == 1 && separator.starts_with(*c) => {
+                parts.push(&tokens[start..i]);
+                start = i + 1;
+            }
+            Token::Word(word) if depth == 0 && word.eq_ignore_ascii_case(separator) => {
+                parts.push(&tokens[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&tokens[start..]);
+
+    parts.into_iter().filter(|part| !part.is_empty()).collect()
+}
+
+fn identifier(token: &Token) -> Option<String> {
+    match token {
+        Token::Word(word) => Some(word.to_lowercase()),
+        Token::Quoted(quoted) => Some(quoted.clone()),
+        _ => None,
+    }
+}
+
+fn is_word(token: Option<&Token>, keyword: &str) -> bool {
+    matches!(token, Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword))
+}
+
+fn render(tokens: &[Token]) -> String {
+    tokens
+        .iter()
+        .map(|token| match token {
+            Token::Word(word) => word.to_lowercase(),
+            Token::Quoted(quoted) => quoted.clone(),
+            Token::Literal(literal) => format!("'{literal}'"),
+            Token::Symbol(c) => c.to_string(),
+        })
+        .collect()
+}
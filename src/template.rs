@@ -0,0 +1,80 @@
+use crate::cql;
+use crate::local::{self, TEMPLATES_DIR};
+use anyhow::Result;
+use std::path::Path;
+
+/// Names of the templates `generate --template` knows.
+pub const BUILTIN: [&str; 1] = ["time-series"];
+
+/// up.cql and down.cql of a migration named `name` made from the built in `template`.
+pub fn builtin(template: &str, name: &str) -> Option<(String, String)> {
+    let table = identifier(name);
+
+    match template {
+        "time-series" => Some((
+            format!(
+                "-- Time series: bucket the partition key so partitions stay bounded, keep the TTL and the compaction
+-- window aligned (around 20 to 30 windows per TTL), and never update or delete rows out of order.
+CREATE TABLE IF NOT EXISTS keyspace_name.{table} (
+    id uuid,
+    bucket date,
+    ts timestamp,
+    value text,
+    PRIMARY KEY ((id, bucket), ts)
+) WITH CLUSTERING ORDER BY (ts DESC)
+    AND compaction = {{'class': 'TimeWindowCompactionStrategy', 'compaction_window_unit': 'DAYS', 'compaction_window_size': 1}}
+    AND default_time_to_live = 2592000;
+"
+            ),
+            format!("DROP TABLE IF EXISTS keyspace_name.{table};\n"),
+        )),
+        _ => None,
+    }
+}
+
+/// Names of the user templates: the subdirectories of `templates/` inside the migrations directory, sorted.
+pub fn user_templates(dir_path: &str) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(format!("{dir_path}/{TEMPLATES_DIR}")) else {
+        return vec![];
+    };
+
+    let mut templates: Vec<String> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if !path.is_dir() {
+                return None;
+            }
+            Some(path.file_name()?.to_str()?.to_string())
+        })
+        .collect();
+
+    templates.sort();
+    templates
+}
+
+/// up.cql and down.cql of a migration named `name` made from the user template `templates/<template>/`.
+/// `{{name}}` is replaced by the migration name, `{{table}}` by a table name derived from it.
+/// A template without down.cql gives an empty one. `None` when there is no such template.
+pub fn user(dir_path: &str, template: &str, name: &str) -> Result<Option<(String, String)>> {
+    let template_dir = format!("{dir_path}/{TEMPLATES_DIR}/{template}");
+    if !Path::new(&template_dir).is_dir() {
+        return Ok(None);
+    }
+
+    let render = |file: &str| -> Result<String> {
+        let path = format!("{template_dir}/{file}");
+        if !Path::new(&path).is_file() {
+            return Ok(String::new());
+        }
+        let contents = local::file_contents(&path).map_err(|e| anyhow::anyhow!("Unable to read template [{path}]: {e}"))?;
+        Ok(contents.replace("{{name}}", name).replace("{{table}}", &identifier(name)))
+    };
+
+    Ok(Some((render("up.cql")?, render("down.cql")?)))
+}
+
+/// Table name derived from a migration name, quoted when needed.
+fn identifier(name: &str) -> String {
+    let table: String = name.chars().map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { '_' }).collect();
+    cql::quote(&table)
+}
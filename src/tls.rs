@@ -0,0 +1,135 @@
+use anyhow::Result;
+use openssl::pkcs12::Pkcs12;
+use openssl::ssl::{SslContext, SslContextBuilder, SslFiletype, SslMethod, SslVerifyMode};
+use openssl::x509::X509;
+use std::fs::{read, read_dir};
+use std::path::Path;
+
+#[derive(Debug, Default, Clone)]
+pub struct TlsOptions {
+    pub enabled: bool,
+    pub insecure_skip_verify: bool,
+    pub ca_cert: String,
+    pub client_cert: String,
+    pub client_key: String,
+    pub client_pkcs12: String,
+    pub client_pkcs12_password: String,
+}
+
+impl TlsOptions {
+    fn has_client_identity(&self) -> bool {
+        !self.client_cert.is_empty() || !self.client_key.is_empty() || !self.client_pkcs12.is_empty()
+    }
+}
+
+pub const INSECURE_WARNING: &str = "
+!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!
+!! WARNING: TLS certificate verification is DISABLED                  !!
+!! (--tls-insecure-skip-verify). The cluster identity is NOT checked. !!
+!! Never use this outside of lab clusters.                            !!
+!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!
+";
+
+/// Builds the TLS context for cluster connections.
+/// Returns `None` when TLS is neither enabled nor implied by another TLS option.
+pub fn context(options: &TlsOptions) -> Result<Option<SslContext>> {
+    if !options.enabled
+        && !options.insecure_skip_verify
+        && options.ca_cert.is_empty()
+        && !options.has_client_identity()
+    {
+        return Ok(None);
+    }
+
+    let mut builder = SslContextBuilder::new(SslMethod::tls())?;
+
+    if options.insecure_skip_verify {
+        log::warn!("{INSECURE_WARNING}");
+        builder.set_verify(SslVerifyMode::NONE);
+    } else {
+        builder.set_verify(SslVerifyMode::PEER);
+    }
+
+    if options.ca_cert.is_empty() {
+        builder.set_default_verify_paths()?;
+    } else {
+        trusted_certificates(&mut builder, &options.ca_cert)?;
+    }
+
+    client_identity(&mut builder, options)?;
+
+    Ok(Some(builder.build()))
+}
+
+/// Trusts only the certificates found at `path` instead of the system roots.
+/// `path` is either a PEM bundle or a directory of PEM bundles.
+fn trusted_certificates(builder: &mut SslContextBuilder, path: &str) -> Result<()> {
+    let location = Path::new(path);
+    let files: Vec<_> = if location.is_dir() {
+        let mut files: Vec<_> = read_dir(location)?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.is_file())
+            .collect();
+        files.sort();
+        files
+    } else {
+        vec![location.to_path_buf()]
+    };
+
+    let mut count = 0;
+    for file in files {
+        let pem = read(&file).map_err(|e| anyhow::anyhow!("Unable to read CA file [{}]: {e}", file.display()))?;
+        let certs = X509::stack_from_pem(&pem)
+            .map_err(|e| anyhow::anyhow!("Unable to parse CA file [{}]: {e}", file.display()))?;
+
+        for cert in certs {
+            builder.cert_store_mut().add_cert(cert)?;
+            count += 1;
+        }
+    }
+
+    if count == 0 {
+        return Err(anyhow::anyhow!("No CA certificates found in [{path}]"));
+    }
+
+    Ok(())
+}
+
+/// Loads the client certificate for mutual TLS, either from a PEM cert/key pair or a PKCS#12 bundle.
+fn client_identity(builder: &mut SslContextBuilder, options: &TlsOptions) -> Result<()> {
+    if !options.client_pkcs12.is_empty() {
+        if !options.client_cert.is_empty() || !options.client_key.is_empty() {
+            return Err(anyhow::anyhow!("Client certificate can either be PEM or PKCS#12, not both"));
+        }
+
+        let path = options.client_pkcs12.as_str();
+        let der = read(path).map_err(|e| anyhow::anyhow!("Unable to read PKCS#12 file [{path}]: {e}"))?;
+        let identity = Pkcs12::from_der(&der)?
+            .parse2(&options.client_pkcs12_password)
+            .map_err(|e| anyhow::anyhow!("Unable to parse PKCS#12 file [{path}]: {e}"))?;
+
+        let cert = identity.cert.ok_or_else(|| anyhow::anyhow!("No certificate in PKCS#12 file [{path}]"))?;
+        let key = identity.pkey.ok_or_else(|| anyhow::anyhow!("No private key in PKCS#12 file [{path}]"))?;
+        builder.set_certificate(&cert)?;
+        builder.set_private_key(&key)?;
+        for ca in identity.ca.into_iter().flatten() {
+            builder.add_extra_chain_cert(ca)?;
+        }
+    } else if options.has_client_identity() {
+        if options.client_cert.is_empty() || options.client_key.is_empty() {
+            return Err(anyhow::anyhow!("Both client certificate and client key are required for mutual TLS"));
+        }
+
+        builder.set_certificate_chain_file(&options.client_cert)
+            .map_err(|e| anyhow::anyhow!("Unable to load client certificate [{}]: {e}", options.client_cert))?;
+        builder.set_private_key_file(&options.client_key, SslFiletype::PEM)
+            .map_err(|e| anyhow::anyhow!("Unable to load client key [{}]: {e}", options.client_key))?;
+    } else {
+        return Ok(());
+    }
+
+    builder.check_private_key()
+        .map_err(|e| anyhow::anyhow!("Client key does not match client certificate: {e}"))?;
+
+    Ok(())
+}
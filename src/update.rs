@@ -0,0 +1,136 @@
+use openssl::hash::{hash, MessageDigest};
+use openssl::pkey::{Id, PKey};
+use openssl::sign::Verifier;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::process::Command;
+
+/// Where `self-update` looks for releases by default: `<url>/<asset>` serves the latest one.
+pub const DEFAULT_RELEASE_URL: &str = "https://github.com/rctrj/scylladb-migrate/releases/latest/download";
+
+/// Lists `<sha256>  <asset>` lines for every asset of the release.
+const CHECKSUMS: &str = "SHA256SUMS";
+
+#[derive(Debug, Clone, Default)]
+pub struct UpdateOptions {
+    /// Base url of the release assets. `DEFAULT_RELEASE_URL` when empty.
+    pub release_url: String,
+    /// PEM public key the checksums file signature (`SHA256SUMS.sig`) is verified with.
+    /// Required unless `insecure_skip_signature` is set.
+    pub public_key: Option<String>,
+    /// Updates without a release key, trusting the checksums file served next to the binary.
+    pub insecure_skip_signature: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Update {
+    UpToDate,
+    Updated { path: PathBuf },
+}
+
+/// Name of the release asset for this platform, e.g. `scylladb-migrate-x86_64-linux`.
+pub fn asset() -> String {
+    format!("scylladb-migrate-{}-{}", std::env::consts::ARCH, std::env::consts::OS)
+}
+
+/// Downloads the release asset for this platform, verifies it and replaces the running executable with it.
+/// Downloads with `curl`, which bastion hosts have when they don't have cargo. Refuses to update without a release
+/// key, as a checksum from the server serving the binary proves nothing against that server, unless the signature
+/// check is skipped explicitly.
+pub async fn self_update(options: &UpdateOptions) -> anyhow::Result<Update> {
+    if options.public_key.is_none() && !options.insecure_skip_signature {
+        return Err(anyhow::anyhow!(
+            "No release key to verify the release with. Pass --release-key <pem>, or --insecure-skip-signature to trust the server"
+        ));
+    }
+
+    let base = if options.release_url.is_empty() { DEFAULT_RELEASE_URL } else { options.release_url.trim_end_matches('/') };
+    let asset = asset();
+
+    let checksums = download(&format!("{base}/{CHECKSUMS}")).await?;
+    match &options.public_key {
+        Some(public_key) => {
+            let signature = download(&format!("{base}/{CHECKSUMS}.sig")).await?;
+            verify_signature(public_key, &checksums, &signature)?;
+        }
+        None => log::warn!("warning: skipping the signature check, only verifying the checksum"),
+    }
+
+    let checksums = String::from_utf8(checksums).map_err(|_| anyhow::anyhow!("{CHECKSUMS} is not text"))?;
+    let expected = checksums
+        .lines()
+        .filter_map(|line| line.split_once(char::is_whitespace))
+        .find(|(_, name)| name.trim().trim_start_matches('*') == asset)
+        .map(|(sum, _)| sum.to_lowercase())
+        .ok_or_else(|| anyhow::anyhow!("No release asset for this platform: [{asset}]"))?;
+
+    let current = std::env::current_exe()?;
+    if fs::read(&current).is_ok_and(|binary| sha256(&binary).is_ok_and(|sum| sum == expected)) {
+        return Ok(Update::UpToDate);
+    }
+
+    let binary = download(&format!("{base}/{asset}")).await?;
+    let actual = sha256(&binary)?;
+    if actual != expected {
+        return Err(anyhow::anyhow!("Checksum mismatch for [{asset}]: expected {expected}, got {actual}"));
+    }
+
+    replace(&current, &binary)?;
+    Ok(Update::Updated { path: current })
+}
+
+async fn download(url: &str) -> anyhow::Result<Vec<u8>> {
+    let output = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location", url])
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(|e| anyhow::anyhow!("Unable to run curl: {e}"))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow::anyhow!("Unable to download [{url}]: {}", stderr.trim()));
+    }
+
+    Ok(output.stdout)
+}
+
+fn sha256(data: &[u8]) -> anyhow::Result<String> {
+    let digest = hash(MessageDigest::sha256(), data)?;
+    Ok(digest.iter().map(|b| format!("{b:02x}")).collect())
+}
+
+fn verify_signature(public_key: &str, data: &[u8], signature: &[u8]) -> anyhow::Result<()> {
+    let pem = fs::read(public_key).map_err(|e| anyhow::anyhow!("Unable to read release key [{public_key}]: {e}"))?;
+    let key = PKey::public_key_from_pem(&pem)?;
+
+    let valid = if key.id() == Id::ED25519 {
+        Verifier::new_without_digest(&key)?.verify_oneshot(signature, data)?
+    } else {
+        let mut verifier = Verifier::new(MessageDigest::sha256(), &key)?;
+        verifier.update(data)?;
+        verifier.verify(signature)?
+    };
+
+    if !valid {
+        return Err(anyhow::anyhow!("Invalid signature of {CHECKSUMS}, refusing to update"));
+    }
+
+    Ok(())
+}
+
+/// Writes next to `current` and renames over it, so a failure midway leaves the old executable in place.
+fn replace(current: &Path, binary: &[u8]) -> anyhow::Result<()> {
+    let staged = current.with_extension("new");
+    fs::write(&staged, binary).map_err(|e| anyhow::anyhow!("Unable to write [{}]: {e}", staged.display()))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&staged, fs::Permissions::from_mode(0o755))?;
+    }
+
+    fs::rename(&staged, current).map_err(|e| anyhow::anyhow!("Unable to replace [{}]: {e}", current.display()))?;
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use crate::db::MigrationData;
+use crate::local;
+use std::fmt::{Display, Formatter};
+
+/// Something to fix before the migrations directory and the tracker can be trusted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// Applied, but its directory is gone.
+    Missing { migration: String },
+    /// Applied, but its up.cql is gone.
+    MissingUp { migration: String },
+    /// Its up.cql changed since it was applied.
+    Modified { migration: String },
+    /// Not named `YYYY-MM-DD-HHMMSS_name`, so its place in the apply order is a guess.
+    MalformedName { migration: String },
+    /// Left behind by a failed run.
+    Failed { migration: String },
+    /// An up part without its `.down.cql` match, or the other way round.
+    UnmatchedPart { migration: String, part: String },
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Problem::Missing { migration } => write!(f, "{migration}: applied, but missing locally"),
+            Problem::MissingUp { migration } => write!(f, "{migration}: applied, but up.cql is missing"),
+            Problem::Modified { migration } => write!(f, "{migration}: up.cql changed since it was applied"),
+            Problem::MalformedName { migration } => write!(f, "{migration}: not named YYYY-MM-DD-HHMMSS_name"),
+            Problem::Failed { migration } => write!(f, "{migration}: failed row in the tracker"),
+            Problem::UnmatchedPart { migration, part } => write!(f, "{migration}: {part} has no matching up or down part"),
+        }
+    }
+}
+
+/// Compares the `local` migrations of `dir_path` with the tracker `rows`. Rows of migrations a local one
+/// squashes are fine, the next `up` replaces them.
+pub(crate) fn validate(dir_path: &str, local: &[String], rows: &[MigrationData]) -> Vec<Problem> {
+    let squashed: Vec<String> = local.iter().flat_map(|migration| local::squashed(dir_path, migration)).collect();
+    let mut problems: Vec<Problem> = local
+        .iter()
+        .filter(|migration| !local::is_well_formed(migration))
+        .map(|migration| Problem::MalformedName { migration: migration.clone() })
+        .collect();
+    for migration in local {
+        problems.extend(
+            local::unmatched_parts(dir_path, migration)
+                .into_iter()
+                .map(|part| Problem::UnmatchedPart { migration: migration.clone(), part }),
+        );
+    }
+
+    for row in rows {
+        let migration = row.id.clone();
+        if row.status == "rolled_back" {
+            continue;
+        } else if row.status != "success" {
+            problems.push(Problem::Failed { migration });
+        } else if !local.contains(&migration) && !squashed.contains(&migration) {
+            problems.push(Problem::Missing { migration });
+        } else if local::parts(&local::file_path(dir_path, &migration, "up.cql")).is_empty() {
+            problems.push(Problem::MissingUp { migration });
+        }
+    }
+
+    problems.extend(modified(dir_path, rows).into_iter().map(|migration| Problem::Modified { migration }));
+    problems
+}
+
+/// Applied migrations whose up.cql no longer matches the checksum recorded when it ran.
+/// Rows without a checksum, written by older versions, are not compared.
+pub(crate) fn modified(dir_path: &str, rows: &[MigrationData]) -> Vec<String> {
+    rows.iter()
+        .filter(|row| row.status == "success")
+        .filter(|row| {
+            let Some(recorded) = &row.checksum else {
+                return false;
+            };
+            local::checksum(dir_path, &row.id).is_some_and(|checksum| checksum != *recorded)
+        })
+        .map(|row| row.id.clone())
+        .collect()
+}